@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Where a sequence of states starts repeating: the state after `start` steps
+/// is the first one seen again, `length` steps later.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Cycle {
+    pub start: usize,
+    pub length: usize
+}
+
+impl Cycle {
+    /// The step with the same state as step `n`, before the cycle repeats.
+    pub fn equivalent_step(&self, n: usize) -> usize {
+        if n < self.start {
+            n
+        } else {
+            self.start + (n - self.start) % self.length
+        }
+    }
+}
+
+/// Floyd's tortoise and hare. Only keeps two states at a time, but steps the
+/// sequence about three times as often as it has states before repeating.
+pub fn floyd<S: PartialEq + Clone>(initial: &S, mut step: impl FnMut(&S) -> S) -> Cycle {
+    let mut tortoise = step(initial);
+    let mut hare = step(&tortoise);
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        hare = step(&hare);
+    }
+
+    let mut start = 0;
+    tortoise = initial.clone();
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+
+    let mut length = 1;
+    hare = step(&tortoise);
+    while tortoise != hare {
+        hare = step(&hare);
+        length += 1;
+    }
+
+    Cycle { start, length }
+}
+
+/// Brent's algorithm. Only keeps two states at a time and steps the sequence
+/// fewer times than Floyd's.
+pub fn brent<S: PartialEq + Clone>(initial: &S, mut step: impl FnMut(&S) -> S) -> Cycle {
+    let (mut power, mut length) = (1, 1);
+    let mut tortoise = initial.clone();
+    let mut hare = step(initial);
+    while tortoise != hare {
+        if power == length {
+            tortoise = hare.clone();
+            power *= 2;
+            length = 0;
+        }
+        hare = step(&hare);
+        length += 1;
+    }
+
+    tortoise = initial.clone();
+    hare = initial.clone();
+    for _ in 0..length {
+        hare = step(&hare);
+    }
+
+    let mut start = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+
+    Cycle { start, length }
+}
+
+/// Steps the sequence once per state, indexing every state seen by its hash.
+/// Returns the cycle along with every state up to where it repeats.
+pub fn hashed<S: Hash + Eq + Clone>(initial: &S, step: impl FnMut(&S) -> S) -> (Cycle, Vec<S>) {
+    hashed_within(initial, step, usize::MAX).unwrap()
+}
+
+/// Like [`hashed`], but gives up if no state repeats within `max_steps` steps.
+/// States are stepped strictly in order, one step per state.
+pub fn hashed_within<S: Hash + Eq + Clone>(initial: &S, mut step: impl FnMut(&S) -> S, max_steps: usize) -> Option<(Cycle, Vec<S>)> {
+    let mut seen = HashMap::from([(initial.clone(), 0)]);
+    let mut states = vec![initial.clone()];
+    for _ in 0..max_steps {
+        let next = step(states.last().unwrap());
+        if let Some(&start) = seen.get(&next) {
+            return Some((Cycle { start, length: states.len() - start }, states))
+        }
+        seen.insert(next.clone(), states.len());
+        states.push(next);
+    }
+
+    None
+}
+
+/// The state after `n` steps, found with [`brent`] and then stepping only up
+/// to the equivalent step within the first pass through the cycle.
+pub fn nth_state<S: PartialEq + Clone>(initial: &S, mut step: impl FnMut(&S) -> S, n: usize) -> S {
+    let cycle = brent(initial, &mut step);
+
+    (0..cycle.equivalent_step(n)).fold(initial.clone(), |state, _| step(&state))
+}
+
+/// The state after `n` steps, found with [`hashed`] so no step is repeated.
+pub fn nth_state_hashed<S: Hash + Eq + Clone>(initial: &S, step: impl FnMut(&S) -> S, n: usize) -> S {
+    let (cycle, mut states) = hashed(initial, step);
+
+    states.swap_remove(cycle.equivalent_step(n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(x: &u64) -> u64 {
+        (x * x + 1) % 255
+    }
+
+    #[test]
+    fn cycle_1() {
+        let expected = Cycle { start: 2, length: 6 };
+        assert_eq!(floyd(&3, step), expected);
+        assert_eq!(brent(&3, step), expected);
+        assert_eq!(hashed(&3, step).0, expected);
+        assert_eq!(hashed_within(&3, step, 8).map(|(cycle, _)| cycle), Some(expected));
+        assert_eq!(hashed_within(&3, step, 7), None);
+    }
+
+    #[test]
+    fn nth_state_1() {
+        for n in [0, 1, 5, 100, 1_001] {
+            let expected = (0..n).fold(3, |x, _| step(&x));
+            assert_eq!(nth_state(&3, step, n), expected);
+            assert_eq!(nth_state_hashed(&3, step, n), expected);
+        }
+        assert_eq!(nth_state(&3, step, 1_000_000_000), nth_state_hashed(&3, step, 1_000_000_000));
+    }
+}
@@ -4,6 +4,12 @@ extern crate aoc_runner;
 extern crate aoc_runner_derive;
 extern crate crypto;
 
+/// Cycle detection for simulations that eventually repeat a state.
+pub mod cycle;
+/// Areas and lattice point counts of polygons with integer vertices.
+pub mod polygon;
 pub mod solutions;
+#[cfg(test)]
+mod test_random;
 
 aoc_lib!{ year = 2023 }
\ No newline at end of file
@@ -0,0 +1,120 @@
+use num::integer::gcd;
+use num::ToPrimitive;
+
+/// A closed polygon on the integer lattice. Coordinates are widened to `i128`
+/// and every measurement is overflow checked, returning `None` if it does not
+/// fit.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Polygon {
+    vertices: Vec<(i128, i128)>
+}
+
+impl Polygon {
+    /// The polygon through `vertices` in order, closing back to the first.
+    pub fn new<T: ToPrimitive>(vertices: impl IntoIterator<Item = (T, T)>) -> Option<Self> {
+        let vertices = vertices
+            .into_iter()
+            .map(|(x, y)| Some((x.to_i128()?, y.to_i128()?)))
+            .collect::<Option<_>>()?;
+
+        Some(Polygon { vertices })
+    }
+
+    /// The polygon traced from the origin by moving `distance` times along each
+    /// `(dx, dy)` in turn.
+    pub fn from_steps<T: ToPrimitive>(steps: impl IntoIterator<Item = ((T, T), T)>) -> Option<Self> {
+        let mut vertices = Vec::new();
+        let mut curr = (0i128, 0i128);
+        for ((dx, dy), distance) in steps {
+            let distance = distance.to_i128()?;
+            curr = (
+                curr.0.checked_add(dx.to_i128()?.checked_mul(distance)?)?,
+                curr.1.checked_add(dy.to_i128()?.checked_mul(distance)?)?
+            );
+            vertices.push(curr);
+        }
+
+        Some(Polygon { vertices })
+    }
+
+    pub fn vertices(&self) -> &[(i128, i128)] {
+        &self.vertices
+    }
+
+    fn edges(&self) -> impl Iterator<Item = ((i128, i128), (i128, i128))> + '_ {
+        self.vertices
+            .iter()
+            .copied()
+            .zip(self.vertices.iter().copied().cycle().skip(1))
+    }
+
+    /// Twice the enclosed area, from the shoelace formula. Doubling keeps it an
+    /// integer for polygons with diagonal edges.
+    pub fn double_area(&self) -> Option<i128> {
+        self.edges()
+            .try_fold(0i128, |area, ((x1, y1), (x2, y2))|
+                area.checked_add(x1.checked_mul(y2)?.checked_sub(x2.checked_mul(y1)?)?)
+            )?
+            .checked_abs()
+    }
+
+    /// Lattice points on the edges, which for edges along the axes is also
+    /// the length of the boundary.
+    pub fn boundary_points(&self) -> Option<i128> {
+        self.edges()
+            .try_fold(0i128, |points, ((x1, y1), (x2, y2))|
+                points.checked_add(gcd(x2.checked_sub(x1)?.checked_abs()?, y2.checked_sub(y1)?.checked_abs()?))
+            )
+    }
+
+    /// Lattice points strictly inside the polygon, by Pick's theorem:
+    /// `A = I + B/2 - 1`.
+    pub fn interior_points(&self) -> Option<i128> {
+        let double_area = self.double_area()?;
+        let boundary = self.boundary_points()?;
+
+        Some(double_area.checked_sub(boundary)?.checked_add(2)? / 2)
+    }
+
+    /// Lattice points inside or on the polygon.
+    pub fn lattice_points(&self) -> Option<i128> {
+        self.interior_points()?.checked_add(self.boundary_points()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polygon_1() {
+        let square = Polygon::new([(0u8, 0), (4, 0), (4, 4), (0, 4)]).unwrap();
+        assert_eq!(square.double_area(), Some(32));
+        assert_eq!(square.boundary_points(), Some(16));
+        assert_eq!(square.interior_points(), Some(9));
+        assert_eq!(square.lattice_points(), Some(25));
+
+        let triangle = Polygon::from_steps([((1i32, 0), 4), ((-1, 1), 4), ((0, -1), 4)]).unwrap();
+        assert_eq!(triangle.vertices(), &[(4, 0), (0, 4), (0, 0)]);
+        assert_eq!(triangle.double_area(), Some(16));
+        assert_eq!(triangle.boundary_points(), Some(12));
+        assert_eq!(triangle.interior_points(), Some(3));
+    }
+
+    #[test]
+    fn polygon_2() {
+        assert_eq!(Polygon::new([(usize::MAX, 0)]).map(|polygon| polygon.vertices().len()), Some(1));
+        assert_eq!(Polygon::new([(u128::MAX, 0)]), None);
+
+        let huge = Polygon::new([(0, 0), (i128::MAX, 0), (i128::MAX, i128::MAX)]).unwrap();
+        assert_eq!(huge.double_area(), None);
+        assert_eq!(huge.lattice_points(), None);
+
+        let most_negative = Polygon::new([(0, 0), (i128::MIN, 1), (0, 1)]).unwrap();
+        assert_eq!(most_negative.double_area(), None);
+
+        let longest_edge = Polygon::new([(0i128, 0i128), (i128::MIN, 0), (0, 1)]).unwrap();
+        assert_eq!(longest_edge.boundary_points(), None);
+        assert_eq!(longest_edge.interior_points(), None);
+    }
+}
@@ -1,4 +1,5 @@
 use std::fmt::Debug;
+use crate::polygon::Polygon;
 
 #[derive(PartialEq, Clone)]
 enum Pipe {
@@ -94,10 +95,10 @@ fn get_next_options(curr_direction: &Direction) -> Vec<Pipe> {
 fn get_next_index(curr_direction: &Direction, curr_x: usize, curr_y: usize, x_len: usize, y_len: usize) -> Option<(usize, usize)> {
     match curr_direction {
         Direction::Up => {
-            (curr_x > 0).then_some((curr_x - 1, curr_y))
+            (curr_x > 0).then(|| (curr_x - 1, curr_y))
         },
         Direction::Left => {
-            (curr_y > 0).then_some((curr_x, curr_y - 1))
+            (curr_y > 0).then(|| (curr_x, curr_y - 1))
         },
         Direction::Down => {
             let next_x = curr_x + 1;
@@ -138,176 +139,52 @@ fn get_next_direction(prev_direction: &Direction, curr_pipe: &Pipe) -> Direction
     }
 }
 
-#[aoc(day10, part1)]
-fn part_one((start, map): &((usize, usize), Vec<Vec<Pipe>>)) -> i32 {
+/// Tiles of the loop through the start, in the order they are walked,
+/// ending back on the start.
+fn find_loop((start, map): &((usize, usize), Vec<Vec<Pipe>>)) -> Vec<(usize, usize)> {
     let (x_len, y_len) = (map.len(), map[0].len());
     let cardnal_directions = vec![
         Direction::Up,
         Direction::Down,
         Direction::Left,
     ];
-    for mut curr_direction in cardnal_directions {
+    'directions: for mut curr_direction in cardnal_directions {
         let (mut curr_x, mut curr_y) = start;
-        let mut count = 0;
+        let mut tiles = Vec::new();
         loop {
             (curr_x, curr_y) = if let Some((x, y)) = get_next_index(&curr_direction, curr_x, curr_y, x_len, y_len) {
                 (x, y)
             } else {
-                count = 0;
-                break
+                continue 'directions
             };
             let next_options = get_next_options(&curr_direction);
             let curr_pipe = &map[curr_x][curr_y];
             if !next_options.contains(curr_pipe) {
-                count = 0;
-                break
+                continue 'directions
             }
-            count += 1;
-            if curr_pipe == &Pipe::Start { break }
-            curr_direction = get_next_direction(&curr_direction, curr_pipe);
-        }
-        if count > 0 {
-            return count / 2
-        }
-    }
-
-    panic!("Should have found a solution")
-}
-
-#[aoc(day10, part2)]
-fn part_two((start, map): &((usize, usize), Vec<Vec<Pipe>>)) -> i32 {
-    let (x_len, y_len) = (map.len(), map[0].len());
-    let cardnal_directions = vec![
-        Direction::Up,
-        Direction::Down,
-        Direction::Left,
-    ];
-
-    let mut pipe_map = (0..x_len)
-        .map(|_| (0..y_len)
-            .map(|_| Pipe::Ground)
-            .collect::<Vec<_>>()
-        )
-        .collect::<Vec<_>>();
-    for mut curr_direction in cardnal_directions {
-        pipe_map
-            .iter_mut()
-            .for_each(|row| {
-                row
-                    .iter_mut()
-                    .for_each(|column| { *column = Pipe::Ground });
-            });
-        let (mut curr_x, mut curr_y) = start;
-        let mut count = 0;
-        loop {
-            (curr_x, curr_y) = if let Some((x, y)) = get_next_index(&curr_direction, curr_x, curr_y, x_len, y_len) {
-                (x, y)
-            } else {
-                count = 0;
-                break
-            };
-            let next_options = get_next_options(&curr_direction);
-            let curr_pipe = &map[curr_x][curr_y];
-            if !next_options.contains(curr_pipe) {
-                count = 0;
-                break
-            }
-            count += 1;
-            pipe_map[curr_x][curr_y] = curr_pipe.clone();
+            tiles.push((curr_x, curr_y));
             if curr_pipe == &Pipe::Start {
-                let next_dir = if curr_x > 0 && pipe_map[curr_x - 1][curr_y] != Pipe::Ground && curr_direction != Direction::Down {
-                    Direction::Up
-                } else if curr_y > 0 && pipe_map[curr_x][curr_y - 1] != Pipe::Ground && curr_direction != Direction::Right {
-                    Direction::Left
-                } else if curr_x < x_len && pipe_map[curr_x + 1][curr_y] != Pipe::Ground && curr_direction != Direction::Up {
-                    Direction::Down
-                } else {
-                    Direction::Right
-                };
-                pipe_map[curr_x][curr_y] = match (curr_direction, next_dir) {
-                    (Direction::Left, Direction::Right) | (Direction::Right, Direction::Left) => Pipe::Horizontal,
-                    (Direction::Up, Direction::Down) | (Direction::Down, Direction::Up) => Pipe::Vertical,
-                    (Direction::Up, Direction::Left) | (Direction::Left, Direction::Up) => Pipe::UpRight,
-                    (Direction::Up, Direction::Right) | (Direction::Right, Direction::Up) => Pipe::UpLeft,
-                    (Direction::Down, Direction::Left) | (Direction::Left, Direction::Down) => Pipe::DownRight,
-                    (Direction::Down, Direction::Right) | (Direction::Right, Direction::Down) => Pipe::DownLeft,
-                    _ => panic!("Start position should not be this")
-                };
-                break
+                return tiles
             }
             curr_direction = get_next_direction(&curr_direction, curr_pipe);
         }
-        if count > 0 {
-            break
-        }
     }
-    pipe_map
-        .iter()
-        .for_each(|row| println!("{row:?}"));
 
-    let next_options = get_next_options(&Direction::Up);
-    let mut area = 0;
-    for (i, row) in pipe_map.iter().enumerate() {
-        for (j, pipe) in row.iter().enumerate() {
-            let mut hit_count = 0;
-            let (mut curr_i, mut curr_j) = (i, j);
-            let mut curr_pipe = &pipe.clone();
-            if *curr_pipe != Pipe::Ground {
-                continue
-            }
-            'outer: loop {
-                if *curr_pipe != Pipe::Ground {
-                    if *curr_pipe == Pipe::Horizontal {
-                        hit_count += 1
-                    } else {
-                        let enter_pipe = (*curr_pipe).clone();
-                        (curr_i, curr_j) = if let Some((x, y)) = get_next_index(&Direction::Up, curr_i, curr_j, x_len, y_len) {
-                            (x, y)
-                        } else {
-                            break 'outer
-                        };
-                        curr_pipe = &pipe_map[curr_i][curr_j];
-                        while *curr_pipe == Pipe::Vertical {
-                            (curr_i, curr_j) = if let Some((x, y)) = get_next_index(&Direction::Up, curr_i, curr_j, x_len, y_len) {
-                                (x, y)
-                            } else {
-                                break 'outer
-                            };
-                            curr_pipe = &pipe_map[curr_i][curr_j];
-                            if !next_options.contains(curr_pipe) && *curr_pipe != Pipe::Ground {
-                                hit_count += 1
-                            }
-                        }
-                        hit_count += match enter_pipe {
-                            Pipe::UpRight => match curr_pipe {
-                                Pipe::DownLeft => 1,
-                                Pipe::DownRight => 2,
-                                _ => panic!("You shouldn't be able to hit this pipe")
-                            },
-                            Pipe::UpLeft => match curr_pipe {
-                                Pipe::DownLeft => 2,
-                                Pipe::DownRight => 1,
-                                _ => panic!("You shouldn't be able to hit this pipe")
-                            },
-                            _ => panic!("You shouldn't be able to hit this pipe")
-                        };
-                    }
-                }
+    panic!("Should have found a solution")
+}
 
-                (curr_i, curr_j) = if let Some((x, y)) = get_next_index(&Direction::Up, curr_i, curr_j, x_len, y_len) {
-                    (x, y)
-                } else {
-                    break
-                };
-                curr_pipe = &pipe_map[curr_i][curr_j];
-            }
-            if hit_count % 2 == 1 {
-                area += 1
-            }
-        }
-    }
+#[aoc(day10, part1)]
+fn part_one(input: &((usize, usize), Vec<Vec<Pipe>>)) -> usize {
+    find_loop(input).len() / 2
+}
 
-    area
+/// The loop runs through the centres of its tiles, so every tile it encloses
+/// is a lattice point strictly inside it.
+#[aoc(day10, part2)]
+fn part_two(input: &((usize, usize), Vec<Vec<Pipe>>)) -> i128 {
+    Polygon::new(find_loop(input))
+        .and_then(|pipe_loop| pipe_loop.interior_points())
+        .expect("Loop is too large to measure")
 }
 
 #[cfg(test)]
@@ -1,10 +1,10 @@
-use std::collections::HashMap;
-
 use itertools::{Itertools, repeat_n};
+use std::ops::{Add, Mul};
+use num::{BigUint, One, Zero};
 use rayon::prelude::*;
 
-#[derive(PartialEq, Clone)]
-enum Status {
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub enum Status {
     Operational,
     Broken,
     Unknown
@@ -100,96 +100,339 @@ fn part_one(input: &[(Vec<Status>, Vec<usize>)]) -> usize {
 
 #[aoc_generator(day12, part2)]
 fn input_generator_two(input: &str) -> Vec<(Vec<Status>, Vec<usize>)> {
-    input
-        .lines()
-        .map(|line| {
-            let (characters, groups) = line.split_once(' ').unwrap();
-            let characters = (0..5).map(|_| characters).join("?");
+    input_generator_one(input)
+        .into_iter()
+        .map(|(sequence, groups)| unfold(&sequence, &groups, 5))
+        .collect()
+}
 
-            let groups: Vec<_> = groups
-                .split(',')
-                .map(|number| number.parse::<usize>().unwrap())
-                .collect();
+/// Repeats the line `factor` times joined by unknown cells, and the damage
+/// groups `factor` times, as the puzzle does when unfolding the records.
+pub fn unfold(sequence: &[Status], damage_groups: &[usize], factor: usize) -> (Vec<Status>, Vec<usize>) {
+    let sequence = Itertools::intersperse(repeat_n(sequence.to_vec(), factor), vec![Status::Unknown])
+        .flatten()
+        .collect();
+    let damage_groups = repeat_n(damage_groups, factor)
+        .flatten()
+        .copied()
+        .collect();
+
+    (sequence, damage_groups)
+}
 
-            let sequence = characters
-                .chars()
-                .map(|character|
-                    match character {
-                        '.' => Status::Operational,
-                        '#' => Status::Broken,
-                        '?' => Status::Unknown,
-                        _ => panic!("Should not have this character in input")
-                    }
-                )
-                .collect();
+/// The table behind `arrangement_count`: `ways[i * (groups + 1) + j]` is the
+/// number of arrangements of the cells from `i` on holding the groups from `j`
+/// on, given the cell before `i` is operational, filled in from the end of the
+/// line.
+fn arrangement_table<T: Clone + Zero + One>(line: &[Status], damage_groups: &[usize]) -> Vec<T> {
+    let (line_len, group_count) = (line.len(), damage_groups.len());
+    let index = |i: usize, j: usize| i * (group_count + 1) + j;
+
+    let mut ways = vec![T::zero(); (line_len + 1) * (group_count + 1)];
+    ways[index(line_len, group_count)] = T::one();
+    for i in (0..line_len).rev() {
+        for j in (0..=group_count).rev() {
+            let mut total = T::zero();
+            if line[i] != Status::Broken {
+                total = total + ways[index(i + 1, j)].clone();
+            }
+            if let Some(end) = group_end(line, damage_groups, i, j) {
+                total = total + ways[index((end + 1).min(line_len), j + 1)].clone();
+            }
+            ways[index(i, j)] = total;
+        }
+    }
 
-            let groups = repeat_n(groups, 5)
-                .flatten()
-                .collect();
+    ways
+}
 
-            (sequence, groups)
-        })
-        .collect()
+/// Where group `j` ends if it can start at cell `i`: the cells it covers can
+/// all be broken and the cell after it, if any, can be operational.
+fn group_end(line: &[Status], damage_groups: &[usize], i: usize, j: usize) -> Option<usize> {
+    let end = i + damage_groups.get(j)?;
+    let fits = end <= line.len()
+        && line[i..end].iter().all(|status| *status != Status::Operational)
+        && line.get(end) != Some(&Status::Broken);
+
+    fits.then_some(end)
+}
+
+/// Counts the arrangements of the line in any integer type, so large unfolds
+/// can be counted in `u128` or `BigUint`.
+pub fn arrangement_count<T: Clone + Zero + One>(line: &[Status], damage_groups: &[usize]) -> T {
+    arrangement_table(line, damage_groups).swap_remove(0)
 }
 
-fn possible_ways(cache: &mut HashMap<(usize, usize, usize), usize>, s: &[Status], within: Option<usize>, remaining: &[usize]) -> usize {
-    if s.is_empty() {
-      return match (within, remaining.len()) {
-        (None, 0) => 1,
-        (Some(x), 1) if x == remaining[0] => 1,
-        _ => 0
-      };
-    }
-    if within.is_some() && remaining.is_empty() {
-      return 0;
-    }
-
-    let key = (s.len(), within.unwrap_or(0), remaining.len());
-    if let Some(&x) = cache.get(&key) {
-      return x;
-    }
-
-    let ways = match (s[0].clone(), within) {
-      (Status::Operational, Some(x)) if x != remaining[0] => 0,
-      (Status::Operational, Some(_)) => possible_ways(cache, &s[1..], None, &remaining[1..]),
-      (Status::Operational, None)    => possible_ways(cache, &s[1..], None, remaining),
-      (Status::Broken, Some(_)) => possible_ways(cache, &s[1..], within.map(|x| x+1), remaining),
-      (Status::Broken, None)    => possible_ways(cache, &s[1..], Some(1), remaining),
-      (Status::Unknown, Some(x)) => {
-        let mut ans = possible_ways(cache, &s[1..], within.map(|x| x+1), remaining);
-        if x == remaining[0] {
-          ans += possible_ways(cache, &s[1..], None, &remaining[1..])
+/// Arrangements of a record unfolded `factor` times.
+pub fn unfolded_count<T: Clone + Zero + One>(line: &[Status], damage_groups: &[usize], factor: usize) -> T {
+    let (line, damage_groups) = unfold(line, damage_groups, factor);
+    arrangement_count(&line, &damage_groups)
+}
+
+/// Counts arrangements as just "none" or "some", so checking a line never
+/// overflows however many arrangements it has.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Feasible(bool);
+
+impl Add for Feasible {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Feasible(self.0 || other.0)
+    }
+}
+
+impl Mul for Feasible {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Feasible(self.0 && other.0)
+    }
+}
+
+impl Zero for Feasible {
+    fn zero() -> Self {
+        Feasible(false)
+    }
+
+    fn is_zero(&self) -> bool {
+        !self.0
+    }
+}
+
+impl One for Feasible {
+    fn one() -> Self {
+        Feasible(true)
+    }
+}
+
+fn has_arrangement(line: &[Status], damage_groups: &[usize]) -> bool {
+    arrangement_count::<Feasible>(line, damage_groups).0
+}
+
+/// Fills in every unknown cell that has the same status in all arrangements of
+/// the line, or `None` if the line has no arrangements at all.
+pub fn forced_cells(line: &[Status], damage_groups: &[usize]) -> Option<Vec<Status>> {
+    if !has_arrangement(line, damage_groups) {
+        return None
+    }
+
+    let mut forced = line.to_vec();
+    let mut trial = line.to_vec();
+    for (i, status) in line.iter().enumerate() {
+        if *status != Status::Unknown {
+            continue
         }
-        ans
-      }
-      (Status::Unknown, None) =>
-        possible_ways(cache, &s[1..], Some(1), remaining) +
-        possible_ways(cache, &s[1..], None, remaining),
+        trial[i] = Status::Broken;
+        let can_break = has_arrangement(&trial, damage_groups);
+        trial[i] = Status::Operational;
+        let can_operate = has_arrangement(&trial, damage_groups);
+        trial[i] = Status::Unknown;
+
+        forced[i] = match (can_break, can_operate) {
+            (true, false) => Status::Broken,
+            (false, true) => Status::Operational,
+            _ => Status::Unknown
+        };
+    }
+
+    Some(forced)
+}
+
+/// Unknown cells with the same status in every arrangement of the line, by
+/// position, or `None` if the line has no arrangements.
+pub fn determined_cells(line: &[Status], damage_groups: &[usize]) -> Option<Vec<(usize, Status)>> {
+    let forced = forced_cells(line, damage_groups)?;
+
+    Some(
+        line
+            .iter()
+            .zip(forced)
+            .enumerate()
+            .filter(|(_, (status, forced))| **status == Status::Unknown && *forced != Status::Unknown)
+            .map(|(i, (_, forced))| (i, forced))
+            .collect()
+    )
+}
+
+/// Lazily yields every arrangement of a line. Only partial lines that still
+/// have an arrangement are kept, so each step does polynomial work no matter
+/// how many arrangements there are in total.
+pub struct Arrangements<'a> {
+    damage_groups: &'a [usize],
+    partial_lines: Vec<Vec<Status>>
+}
+
+pub fn arrangements<'a>(line: &[Status], damage_groups: &'a [usize]) -> Arrangements<'a> {
+    let partial_lines = if has_arrangement(line, damage_groups) {
+        vec![line.to_vec()]
+    } else {
+        Vec::new()
     };
-    cache.insert(key, ways);
-    ways
+
+    Arrangements { damage_groups, partial_lines }
+}
+
+impl Iterator for Arrangements<'_> {
+    type Item = Vec<Status>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let partial_line = self.partial_lines.pop()?;
+            let Some(i) = partial_line.iter().position(|status| *status == Status::Unknown) else {
+                return Some(partial_line)
+            };
+
+            for guess in [Status::Operational, Status::Broken] {
+                let mut guessed = partial_line.clone();
+                guessed[i] = guess;
+                if has_arrangement(&guessed, self.damage_groups) {
+                    self.partial_lines.push(guessed);
+                }
+            }
+        }
+    }
+}
+
+/// Picks an arrangement uniformly at random. Walking along the line, each
+/// choice between leaving a cell operational and starting the next group there
+/// is made with probability proportional to the arrangements left either way,
+/// using `random(n)` to draw a number in `0..n`. The weights are counted in
+/// `BigUint`, so unfolded lines with huge numbers of arrangements stay exact.
+pub fn sample_arrangement(line: &[Status], damage_groups: &[usize], mut random: impl FnMut(&BigUint) -> BigUint) -> Option<Vec<Status>> {
+    let ways = arrangement_table::<BigUint>(line, damage_groups);
+    let index = |i: usize, j: usize| i * (damage_groups.len() + 1) + j;
+    if ways[0].is_zero() {
+        return None
+    }
+
+    let mut sample = vec![Status::Operational; line.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < line.len() {
+        let operational_ways = if line[i] != Status::Broken { ways[index(i + 1, j)].clone() } else { BigUint::zero() };
+        let group = group_end(line, damage_groups, i, j)
+            .map(|end| (end, &ways[index((end + 1).min(line.len()), j + 1)]))
+            .filter(|(_, group_ways)| !group_ways.is_zero());
+
+        match group {
+            Some((end, group_ways)) if random(&(&operational_ways + group_ways)) >= operational_ways => {
+                sample[i..end].fill(Status::Broken);
+                (i, j) = (end + 1, j + 1);
+            },
+            _ => i += 1
+        }
+    }
+
+    Some(sample)
+}
+
+/// A griddler puzzle: the damage groups along every row and every column.
+#[derive(Debug, PartialEq)]
+pub struct Nonogram {
+    pub rows: Vec<Vec<usize>>,
+    pub cols: Vec<Vec<usize>>
+}
+
+/// Row clues, a blank line, then column clues. Each clue is a line of comma
+/// separated group sizes, with `0` for a line with no groups.
+pub fn parse_nonogram(input: &str) -> Nonogram {
+    let (rows, cols) = input.split_once("\n\n").expect("Should have row and column clues");
+    let parse_clues = |clues: &str| clues
+        .lines()
+        .map(|line|
+            line
+                .split(',')
+                .map(|number| number.trim().parse::<usize>().unwrap())
+                .filter(|group| *group != 0)
+                .collect()
+        )
+        .collect();
+
+    Nonogram { rows: parse_clues(rows), cols: parse_clues(cols) }
+}
+
+impl Nonogram {
+    /// Forces cells line by line until nothing changes, then guesses the first
+    /// cell still unknown and backtracks if the guess leads nowhere.
+    pub fn solve(&self) -> Option<Vec<Vec<Status>>> {
+        let grid = vec![vec![Status::Unknown; self.cols.len()]; self.rows.len()];
+        self.solve_from(grid)
+    }
+
+    fn solve_from(&self, mut grid: Vec<Vec<Status>>) -> Option<Vec<Vec<Status>>> {
+        self.propagate(&mut grid)?;
+
+        let Some((x, y)) = (0..grid.len())
+            .cartesian_product(0..self.cols.len())
+            .find(|(x, y)| grid[*x][*y] == Status::Unknown)
+        else {
+            return Some(grid)
+        };
+
+        [Status::Broken, Status::Operational]
+            .into_iter()
+            .find_map(|guess| {
+                let mut guessed = grid.clone();
+                guessed[x][y] = guess;
+                self.solve_from(guessed)
+            })
+    }
+
+    fn propagate(&self, grid: &mut [Vec<Status>]) -> Option<()> {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (x, damage_groups) in self.rows.iter().enumerate() {
+                let forced = forced_cells(&grid[x], damage_groups)?;
+                changed |= forced != grid[x];
+                grid[x] = forced;
+            }
+            for (y, damage_groups) in self.cols.iter().enumerate() {
+                let col = grid.iter().map(|row| row[y].clone()).collect_vec();
+                let forced = forced_cells(&col, damage_groups)?;
+                changed |= forced != col;
+                for (row, status) in grid.iter_mut().zip(forced) {
+                    row[y] = status;
+                }
+            }
+        }
+
+        Some(())
+    }
+}
+
+pub fn render_nonogram(grid: &[Vec<Status>]) -> String {
+    grid
+        .iter()
+        .map(|row|
+            row
+                .iter()
+                .map(|status| match status {
+                    Status::Operational => '.',
+                    Status::Broken => '#',
+                    Status::Unknown => '?'
+                })
+                .collect::<String>() + "\n"
+        )
+        .collect()
 }
 
 #[aoc(day12, part2)]
 fn part_two(input: &[(Vec<Status>, Vec<usize>)]) -> usize {
-    let mut cache =  HashMap::new();
-
     input
         .iter()
-        .map(|(raw_sequence, damage_groups)| {
-            let sum = possible_ways(&mut cache, raw_sequence, None, damage_groups);
-            cache.clear();
-            sum
-        })
+        .map(|(raw_sequence, damage_groups)| arrangement_count::<usize>(raw_sequence, damage_groups))
         .sum()
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use indoc::indoc;
 
 
     use super::*;
+    use crate::test_random::XorShift;
 
 
     #[test]
@@ -274,4 +517,150 @@ mod tests {
         let result = part_two(&input_generator_two(input));
         assert_eq!(result, 506250);
     }
+
+    #[test]
+    fn forced_cells_1() {
+        let line = input_generator_one("?###???????? 3,2,1").remove(0);
+        let result = render_nonogram(&[forced_cells(&line.0, &line.1).unwrap()]);
+        assert_eq!(result, ".###.???????\n");
+        assert_eq!(forced_cells(&line.0, &[2]), None);
+    }
+
+    #[test]
+    fn nonogram_1() {
+        let input = indoc! {"
+            3
+            1,1
+            5
+            1
+            1
+
+            1
+            3
+            1,3
+            3
+            1
+        "};
+        let result = parse_nonogram(input).solve().unwrap();
+        assert_eq!(render_nonogram(&result), indoc! {"
+            .###.
+            .#.#.
+            #####
+            ..#..
+            ..#..
+        "});
+    }
+
+    #[test]
+    fn nonogram_2() {
+        // Propagation alone cannot place either diagonal
+        let input = indoc! {"
+            1
+            1
+
+            1
+            1
+        "};
+        let result = parse_nonogram(input).solve().unwrap();
+        assert_eq!(render_nonogram(&result), indoc! {"
+            #.
+            .#
+        "});
+    }
+
+    #[test]
+    fn nonogram_3() {
+        let input = indoc! {"
+            2
+            0
+
+            0
+            0
+        "};
+        assert_eq!(parse_nonogram(input).solve(), None);
+    }
+
+    #[test]
+    fn determined_cells_1() {
+        let (line, damage_groups) = input_generator_one(".??..??...?##. 1,1,3").remove(0);
+        assert_eq!(determined_cells(&line, &damage_groups), Some(vec![(10, Status::Broken)]));
+    }
+
+    #[test]
+    fn arrangements_1() {
+        let (line, damage_groups) = input_generator_one("?###???????? 3,2,1").remove(0);
+        let result = arrangements(&line, &damage_groups).collect_vec();
+        assert_eq!(result.len(), 10);
+        assert!(result.iter().all_unique());
+        assert!(result.iter().all(|arrangement| validate_sequence(arrangement.clone(), &damage_groups)));
+        assert_eq!(render_nonogram(&result[..1]), ".###.##.#...\n");
+    }
+
+    #[test]
+    fn sample_arrangement_1() {
+        let (line, damage_groups) = input_generator_one("?###???????? 3,2,1").remove(0);
+        let mut rng = XorShift::new(12);
+        let mut random = |bound: &BigUint| BigUint::from(rng.next_u64()) % bound;
+
+        let mut counts = HashMap::new();
+        for _ in 0..2_000 {
+            let sample = sample_arrangement(&line, &damage_groups, &mut random).unwrap();
+            *counts.entry(render_nonogram(&[sample])).or_insert(0) += 1;
+        }
+        assert_eq!(counts.len(), 10);
+        assert!(counts.values().all(|count| (150..250).contains(count)));
+    }
+
+    #[test]
+    fn long_line_1() {
+        let (line, damage_groups) = input_generator_one("?###???????? 3,2,1").remove(0);
+        let (line, damage_groups) = unfold(&line, &damage_groups, 20);
+        assert!(arrangement_count::<u128>(&line, &damage_groups) > u64::MAX as u128);
+
+        let forced = forced_cells(&line, &damage_groups).unwrap();
+        assert_eq!(forced.iter().filter(|status| **status == Status::Broken).count(), 3 * 20);
+        assert!(arrangements(&line, &damage_groups).next().is_some());
+
+        let mut rng = XorShift::new(12);
+        let mut random = |bound: &BigUint| {
+            let draw = (0..4).fold(BigUint::zero(), |draw, _| (draw << 64u32) + rng.next_u64());
+            draw % bound
+        };
+        let sample = sample_arrangement(&line, &damage_groups, &mut random).unwrap();
+        assert!(validate_sequence(sample, &damage_groups));
+
+        let mut impossible = line.clone();
+        impossible[2] = Status::Operational;
+        assert_eq!(forced_cells(&impossible, &damage_groups), None);
+    }
+
+    #[test]
+    fn unfolded_count_1() {
+        let (line, damage_groups) = input_generator_one("?###???????? 3,2,1").remove(0);
+        assert_eq!(unfolded_count::<usize>(&line, &damage_groups, 1), 10);
+        assert_eq!(unfolded_count::<usize>(&line, &damage_groups, 5), 506250);
+        assert_eq!(
+            unfolded_count::<BigUint>(&line, &damage_groups, 20),
+            BigUint::from(unfolded_count::<u128>(&line, &damage_groups, 20))
+        );
+        assert!(unfolded_count::<BigUint>(&line, &damage_groups, 40) > BigUint::from(u128::MAX));
+    }
+
+    #[test]
+    fn arrangement_count_1() {
+        let input = indoc! {"
+            ???.### 1,1,3
+            .??..??...?##. 1,1,3
+            ?#?#?#?#?#?#?#? 1,3,1,6
+            ????.#...#... 4,1,1
+            ????.######..#####. 1,6,5
+            ?###???????? 3,2,1
+            ?#??.??#?.?? 1,1,2,1
+        "};
+        for line in input_generator_one(input) {
+            assert_eq!(arrangement_count::<usize>(&line.0, &line.1), part_one(&[line.clone()]));
+        }
+        assert_eq!(arrangement_count::<usize>(&vec![Status::Unknown; 3], &[]), 1);
+        assert_eq!(arrangement_count::<usize>(&[Status::Unknown, Status::Broken], &[]), 0);
+    }
 }
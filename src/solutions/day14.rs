@@ -1,9 +1,16 @@
 use core::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+use itertools::Itertools;
+use crate::cycle::nth_state_hashed;
 
-type Map = Vec<Vec<GroundType>>;
+pub type Map = Vec<Vec<GroundType>>;
 
-#[derive(Clone, PartialEq)]
-enum GroundType {
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum GroundType {
     Round,
     Cube,
     Empty
@@ -61,132 +68,345 @@ fn part_one(input: &Map) -> usize {
         .sum()
 }
 
-fn tilt_north(mut map: Map) -> Map {
-    let (row_len, col_len) = (map.len(), map[0].len());
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tilt {
+    North,
+    West,
+    South,
+    East
+}
 
-    (0..col_len)
-        .for_each(|j| {
-            let mut max_location = 0;
-            for i in 0..row_len {
-                let curr_spot = &map[i][j];
-                if *curr_spot == GroundType::Round {
-                    map[i][j] = GroundType::Empty;
-                    map[max_location][j] = GroundType::Round;
-                    max_location += 1;
-                } else if *curr_spot == GroundType::Cube {
-                    max_location = i + 1
-                }
-            }
-        });
+/// Parses tilts written as `N`, `W`, `S` and `E`, ignoring whitespace, so
+/// `NWSE NWSE N` is two spin cycles and a tilt north. Returns the first
+/// character that is not a direction as the error.
+pub fn parse_tilts(input: &str) -> Result<Vec<Tilt>, char> {
+    input
+        .chars()
+        .filter(|character| !character.is_whitespace())
+        .map(|character| match character {
+            'N' => Ok(Tilt::North),
+            'W' => Ok(Tilt::West),
+            'S' => Ok(Tilt::South),
+            'E' => Ok(Tilt::East),
+            _ => Err(character)
+        })
+        .collect()
+}
 
-    map
+/// Total load on each edge of the platform, where a rock weighs one more than
+/// the number of rows or columns between it and the edge.
+#[derive(Debug, PartialEq)]
+pub struct WallLoads {
+    pub north: usize,
+    pub west: usize,
+    pub south: usize,
+    pub east: usize
 }
 
+/// Where the cube rocks sit, as runs of cells between them along every row
+/// and column given by their first cell and length.
+#[derive(PartialEq)]
+struct Layout {
+    height: usize,
+    width: usize,
+    cube_rows: Vec<Line>,
+    row_segments: Vec<Vec<(usize, usize)>>,
+    col_segments: Vec<Vec<(usize, usize)>>
+}
 
-fn tilt_south(mut map: Map) -> Map {
-    let (row_len, col_len) = (map.len(), map[0].len());
+/// The platform packed into one `Line` per row, where bit `j` is column `j`.
+/// Tilting counts the round rocks in each run between cube rocks and packs
+/// them against the end of the run, rather than rolling rocks one at a time.
+#[derive(Clone)]
+pub struct Platform {
+    round_rows: Vec<Line>,
+    layout: Rc<Layout>
+}
 
-    (0..col_len)
-        .for_each(|j| {
-            let mut max_location = col_len - 1;
-            for i in (0..row_len).rev() {
-                let curr_spot = &map[i][j];
-                if *curr_spot == GroundType::Round {
-                    map[i][j] = GroundType::Empty;
-                    map[max_location][j] = GroundType::Round;
-                    max_location = max_location.checked_sub(1).unwrap_or_default()
-                } else if *curr_spot == GroundType::Cube {
-                    max_location = i.checked_sub(1).unwrap_or_default()
-                }
-            }
-        });
+impl PartialEq for Platform {
+    fn eq(&self, other: &Self) -> bool {
+        self.round_rows == other.round_rows
+            && (Rc::ptr_eq(&self.layout, &other.layout) || self.layout == other.layout)
+    }
+}
 
-    map
+impl Eq for Platform {}
+
+// The layout never changes while tilting, so only the round rocks are hashed
+impl Hash for Platform {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.round_rows.hash(state);
+    }
 }
 
+impl Debug for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_map().fmt(f)
+    }
+}
 
-fn tilt_east(mut map: Map) -> Map {
-    let (row_len, col_len) = (map.len(), map[0].len());
+/// A row or column of the platform as a bitset of `u64` blocks, where bit
+/// `j` is cell `j`.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Line(Vec<u64>);
 
-    (0..row_len)
-        .for_each(|i| {
-            let mut max_location = row_len - 1;
-            for j in (0..col_len).rev() {
-                let curr_spot = &map[i][j];
-                if *curr_spot == GroundType::Round {
-                    map[i][j] = GroundType::Empty;
-                    map[i][max_location] = GroundType::Round;
-                    max_location = max_location.checked_sub(1).unwrap_or_default()
-                } else if *curr_spot == GroundType::Cube {
-                    max_location = j.checked_sub(1).unwrap_or_default()
-                }
-            }
-        });
+impl Line {
+    fn empty(len: usize) -> Self {
+        Line(vec![0; len.div_ceil(64)])
+    }
 
-    map
-}
+    fn get(&self, j: usize) -> bool {
+        self.0[j / 64] >> (j % 64) & 1 == 1
+    }
 
-fn tilt_west(mut map: Map) -> Map {
-    let (row_len, col_len) = (map.len(), map[0].len());
+    fn set(&mut self, j: usize) {
+        self.0[j / 64] |= 1 << (j % 64);
+    }
 
-    (0..row_len)
-        .for_each(|i| {
-            let mut max_location = 0;
-            for j in 0..col_len {
-                let curr_spot = &map[i][j];
-                if *curr_spot == GroundType::Round {
-                    map[i][j] = GroundType::Empty;
-                    map[i][max_location] = GroundType::Round;
-                    max_location += 1
-                } else if *curr_spot == GroundType::Cube {
-                    max_location = j + 1
-                }
-            }
-        });
+    fn count_ones(&self) -> usize {
+        self.0.iter().map(|block| block.count_ones() as usize).sum()
+    }
+
+    // Each block overlapping the `len` cells from `start`, with a mask of the
+    // overlapping bits
+    fn run_blocks(start: usize, len: usize) -> impl Iterator<Item = (usize, u64)> {
+        let end = start + len;
+        (start / 64..end.div_ceil(64)).map(move |block| {
+            let low = start.max(block * 64) - block * 64;
+            let high = end.min(block * 64 + 64) - block * 64;
+            (block, (u64::MAX >> (64 - (high - low))) << low)
+        })
+    }
+
+    fn count_run(&self, start: usize, len: usize) -> usize {
+        Line::run_blocks(start, len)
+            .map(|(block, mask)| (self.0[block] & mask).count_ones() as usize)
+            .sum()
+    }
+
+    fn set_run(&mut self, start: usize, len: usize) {
+        for (block, mask) in Line::run_blocks(start, len) {
+            self.0[block] |= mask;
+        }
+    }
+}
 
+fn pack(map: &Map, ground_type: GroundType) -> Vec<Line> {
     map
+        .iter()
+        .map(|row|
+            row
+                .iter()
+                .enumerate()
+                .filter(|(_, curr_spot)| **curr_spot == ground_type)
+                .fold(Line::empty(row.len()), |mut bits, (j, _)| {
+                    bits.set(j);
+                    bits
+                })
+        )
+        .collect()
 }
 
-fn cycle(map: Map) -> Map {
-    tilt_east(
-        tilt_south(
-            tilt_west(
-                tilt_north(map)
-            )
+fn transpose(rows: &[Line], width: usize) -> Vec<Line> {
+    (0..width)
+        .map(|j|
+            rows
+                .iter()
+                .enumerate()
+                .filter(|(_, row)| row.get(j))
+                .fold(Line::empty(rows.len()), |mut bits, (i, _)| {
+                    bits.set(i);
+                    bits
+                })
         )
-    )
+        .collect()
 }
 
-fn get_load_on_support(map: &Map) -> usize {
-    let mut total_load = 0;
-    for (vertical_pos, line) in map.iter().enumerate() {
-        for (_, curr_spot) in line.iter().enumerate() {
-            if *curr_spot == GroundType::Round {
-                total_load += map.len() - vertical_pos;
+fn segments(cubes: &Line, len: usize) -> Vec<(usize, usize)> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for i in 0..=len {
+        if i == len || cubes.get(i) {
+            if i > start {
+                segments.push((start, i - start));
             }
+            start = i + 1;
         }
     }
 
-    total_load
+    segments
 }
 
-#[aoc(day14, part2)]
-fn part_two(input: &Map) -> usize {
-    let mut map = input.clone();
-    let mut seen_states: Vec<Map> = vec![input.clone()];
-
-    loop {
-        map = cycle(map.clone());
-        if let Some(index) = seen_states.iter().position(|x| x == &map) {
-            let cycle_length = seen_states.len() - index;
-            let cycle_start = index;
-            let final_map =
-                &seen_states[cycle_start + (1_000_000_000 - cycle_start) % cycle_length];
-
-            return get_load_on_support(final_map);
+/// Packs the round rocks of each run in `line` to its low end, or high end
+/// if `towards_high` is set.
+fn tilt_line(line: &Line, segments: &[(usize, usize)], towards_high: bool) -> Line {
+    let mut tilted = Line(vec![0; line.0.len()]);
+    for &(start, len) in segments {
+        let rocks = line.count_run(start, len);
+        match (rocks, towards_high) {
+            (0, _) => {},
+            (_, false) => tilted.set_run(start, rocks),
+            (_, true) => tilted.set_run(start + len - rocks, rocks)
         }
-        seen_states.push(map.clone());
     }
+
+    tilted
+}
+
+impl From<&Map> for Platform {
+    fn from(map: &Map) -> Self {
+        let (height, width) = (map.len(), map[0].len());
+
+        let cube_rows = pack(map, GroundType::Cube);
+        let row_segments = cube_rows.iter().map(|row| segments(row, width)).collect();
+        let col_segments = transpose(&cube_rows, width).iter().map(|col| segments(col, height)).collect();
+
+        Platform {
+            round_rows: pack(map, GroundType::Round),
+            layout: Rc::new(Layout { height, width, cube_rows, row_segments, col_segments })
+        }
+    }
+}
+
+impl Platform {
+    pub fn from_input(input: &str) -> Self {
+        Platform::from(&input_generator(input))
+    }
+
+    fn to_map(&self) -> Map {
+        self.round_rows
+            .iter()
+            .zip(self.layout.cube_rows.iter())
+            .map(|(round, cube)|
+                (0..self.layout.width)
+                    .map(|j| match (round.get(j), cube.get(j)) {
+                        (true, _) => GroundType::Round,
+                        (_, true) => GroundType::Cube,
+                        _ => GroundType::Empty
+                    })
+                    .collect()
+            )
+            .collect()
+    }
+
+    fn tilt_rows(&self, towards_high: bool) -> Platform {
+        let round_rows = self.round_rows
+            .iter()
+            .zip(self.layout.row_segments.iter())
+            .map(|(row, segments)| tilt_line(row, segments, towards_high))
+            .collect();
+
+        Platform { round_rows, layout: self.layout.clone() }
+    }
+
+    fn tilt_cols(&self, towards_high: bool) -> Platform {
+        let round_cols = transpose(&self.round_rows, self.layout.width)
+            .into_iter()
+            .zip(self.layout.col_segments.iter())
+            .map(|(col, segments)| tilt_line(&col, segments, towards_high))
+            .collect_vec();
+
+        Platform { round_rows: transpose(&round_cols, self.layout.height), layout: self.layout.clone() }
+    }
+
+    fn tilt_north(&self) -> Platform {
+        self.tilt_cols(false)
+    }
+
+    fn tilt_south(&self) -> Platform {
+        self.tilt_cols(true)
+    }
+
+    fn tilt_west(&self) -> Platform {
+        self.tilt_rows(false)
+    }
+
+    fn tilt_east(&self) -> Platform {
+        self.tilt_rows(true)
+    }
+
+    pub fn tilt(&self, tilt: Tilt) -> Platform {
+        match tilt {
+            Tilt::North => self.tilt_north(),
+            Tilt::West => self.tilt_west(),
+            Tilt::South => self.tilt_south(),
+            Tilt::East => self.tilt_east()
+        }
+    }
+
+    pub fn cycle(&self) -> Platform {
+        self.tilt_north().tilt_west().tilt_south().tilt_east()
+    }
+
+    pub fn get_load_on_support(&self) -> usize {
+        self.wall_loads().north
+    }
+
+    pub fn wall_loads(&self) -> WallLoads {
+        let (height, width) = (self.layout.height, self.layout.width);
+        let row_loads = self.round_rows
+            .iter()
+            .enumerate()
+            .map(|(vertical_pos, row)| (vertical_pos, row.count_ones()));
+        let col_loads = transpose(&self.round_rows, width)
+            .into_iter()
+            .enumerate()
+            .map(|(horizontal_pos, col)| (horizontal_pos, col.count_ones()))
+            .collect_vec();
+
+        WallLoads {
+            north: row_loads.clone().map(|(i, rocks)| rocks * (height - i)).sum(),
+            south: row_loads.map(|(i, rocks)| rocks * (i + 1)).sum(),
+            west: col_loads.iter().map(|(j, rocks)| rocks * (width - j)).sum(),
+            east: col_loads.iter().map(|(j, rocks)| rocks * (j + 1)).sum()
+        }
+    }
+
+    /// Applies each tilt in turn, returning the platform after every step
+    /// along with its wall loads.
+    pub fn apply_tilts(&self, tilts: &[Tilt]) -> Vec<(Platform, WallLoads)> {
+        tilts
+            .iter()
+            .scan(self.clone(), |platform, tilt| {
+                *platform = platform.tilt(*tilt);
+                Some((platform.clone(), platform.wall_loads()))
+            })
+            .collect()
+    }
+
+    pub fn render(&self) -> String {
+        self.to_map()
+            .into_iter()
+            .map(|row| row.iter().map(|curr_spot| format!("{curr_spot:?}")).collect::<String>() + "\n")
+            .collect()
+    }
+
+    /// Redraws the platform in the terminal after each tilt, waiting `delay`
+    /// between frames, and returns where the rocks end up.
+    pub fn replay(&self, tilts: &[Tilt], out: &mut impl Write, delay: Duration) -> io::Result<Platform> {
+        let mut platform = self.clone();
+        for (step, tilt) in tilts.iter().enumerate() {
+            platform = platform.tilt(*tilt);
+            let loads = platform.wall_loads();
+            write!(out, "\x1b[2J\x1b[H{}", platform.render())?;
+            writeln!(
+                out,
+                "Step {}: {tilt:?} | north {} west {} south {} east {}",
+                step + 1, loads.north, loads.west, loads.south, loads.east
+            )?;
+            out.flush()?;
+            thread::sleep(delay);
+        }
+
+        Ok(platform)
+    }
+}
+
+#[aoc(day14, part2)]
+fn part_two(input: &Map) -> usize {
+    let final_platform = nth_state_hashed(&Platform::from(input), Platform::cycle, 1_000_000_000);
+
+    final_platform.get_load_on_support()
 }
 
 #[cfg(test)]
@@ -229,4 +449,181 @@ mod tests {
         let result = part_two(&input_generator(input));
         assert_eq!(result, 64);
     }
+
+    #[test]
+    fn tilt_1() {
+        let input = indoc! {"
+            O....#....
+            O.OO#....#
+            .....##...
+            OO.#O....O
+            .O.....O#.
+            O.#..O.#.#
+            ..O..#O..O
+            .......O..
+            #....###..
+            #OO..#....
+        "};
+        let platform = Platform::from(&input_generator(input));
+        let expected = indoc! {"
+            OOOO.#.O..
+            OO..#....#
+            OO..O##..O
+            O..#.OO...
+            ........#.
+            ..#....#.#
+            ..O..#.O.O
+            ..O.......
+            #....###..
+            #....#....
+        "};
+        let tilted = platform.tilt_north();
+        assert_eq!(tilted, Platform::from(&input_generator(expected)));
+        assert_eq!(tilted.get_load_on_support(), 136);
+        assert_eq!(format!("{tilted:?}"), format!("{:?}", input_generator(expected)));
+    }
+
+    #[test]
+    fn tilt_2() {
+        let (height, width) = (150, 200);
+        let mut map = vec![vec![GroundType::Empty; width]; height];
+        map[0][width - 1] = GroundType::Cube;
+        map[height - 1][0] = GroundType::Round;
+        map[height - 1][width - 1] = GroundType::Round;
+        map[height - 1][70] = GroundType::Round;
+        let platform = Platform::from(&map);
+
+        assert_eq!(platform.tilt_north().wall_loads().north, 150 + 150 + 149);
+        let tilted = platform.tilt_west();
+        assert_eq!(tilted.wall_loads(), WallLoads { north: 3, west: 200 + 199 + 198, south: 450, east: 6 });
+        assert_eq!(tilted.tilt_east().wall_loads().east, 200 + 199 + 198);
+        assert_eq!(platform.cycle().wall_loads(), WallLoads { north: 4, west: 4, south: 449, east: 599 });
+    }
+
+    #[test]
+    fn cycle_1() {
+        let input = indoc! {"
+            O....#....
+            O.OO#....#
+            .....##...
+            OO.#O....O
+            .O.....O#.
+            O.#..O.#.#
+            ..O..#O..O
+            .......O..
+            #....###..
+            #OO..#....
+        "};
+        let expected = [
+            indoc! {"
+                .....#....
+                ....#...O#
+                ...OO##...
+                .OO#......
+                .....OOO#.
+                .O#...O#.#
+                ....O#....
+                ......OOOO
+                #...O###..
+                #..OO#....
+            "},
+            indoc! {"
+                .....#....
+                ....#...O#
+                .....##...
+                ..O#......
+                .....OOO#.
+                .O#...O#.#
+                ....O#...O
+                .......OOO
+                #..OO###..
+                #.OOO#...O
+            "},
+            indoc! {"
+                .....#....
+                ....#...O#
+                .....##...
+                ..O#......
+                .....OOO#.
+                .O#...O#.#
+                ....O#...O
+                .......OOO
+                #...O###.O
+                #.OOO#...O
+            "}
+        ];
+
+        let mut platform = Platform::from(&input_generator(input));
+        for expected in expected {
+            platform = platform.cycle();
+            assert_eq!(format!("{platform:?}"), format!("{:?}", input_generator(expected)));
+        }
+    }
+
+    #[test]
+    fn cycle_2() {
+        let input = indoc! {"
+            O.#O.
+            .O..O
+            #.O.#
+        "};
+        let result = Platform::from(&input_generator(input)).cycle();
+        let expected = indoc! {"
+            .O#..
+            ...OO
+            #.OO#
+        "};
+        assert_eq!(result, Platform::from(&input_generator(expected)));
+    }
+
+    #[test]
+    fn tilts_1() {
+        let input = indoc! {"
+            O....#....
+            O.OO#....#
+            .....##...
+            OO.#O....O
+            .O.....O#.
+            O.#..O.#.#
+            ..O..#O..O
+            .......O..
+            #....###..
+            #OO..#....
+        "};
+        let platform = Platform::from_input(input);
+        let tilts = parse_tilts("NWSE NWSE N").unwrap();
+        assert_eq!(tilts.len(), 9);
+
+        let steps = platform.apply_tilts(&tilts);
+        assert_eq!(steps[0].1.north, 136);
+        assert_eq!(steps[3].0, platform.cycle());
+        assert_eq!(steps[7].0, platform.cycle().cycle());
+        assert_eq!(steps[8].0, platform.cycle().cycle().tilt_north());
+
+        assert_eq!(steps[3].1, WallLoads { north: 87, west: 92, south: 111, east: 106 });
+    }
+
+    #[test]
+    fn tilts_2() {
+        assert_eq!(parse_tilts("N x"), Err('x'));
+    }
+
+    #[test]
+    fn replay_1() {
+        let input = indoc! {"
+            O.#
+            ..O
+        "};
+        let mut out = Vec::new();
+        let result = Platform::from_input(input)
+            .replay(&parse_tilts("SE").unwrap(), &mut out, Duration::ZERO)
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), concat!(
+            "\x1b[2J\x1b[H..#\nO.O\n",
+            "Step 1: South | north 2 west 4 south 4 east 4\n",
+            "\x1b[2J\x1b[H..#\n.OO\n",
+            "Step 2: East | north 2 west 3 south 4 east 5\n"
+        ));
+        assert_eq!(result.render(), "..#\n.OO\n");
+    }
 }
@@ -1,4 +1,9 @@
-type OurMap = Vec<Vec<(String, usize)>>;
+use std::borrow::Borrow;
+use std::collections::{btree_map, BTreeMap};
+use std::fmt::{self, Display};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
+use std::ops::Index;
 
 fn our_hash(input: &str) -> usize {
     let mut hash = 0;
@@ -19,65 +24,515 @@ fn part_one(input: &str) -> usize {
         .sum()
 }
 
-fn delete(key: &str, map: &mut OurMap) -> bool {
-    let index = our_hash(key);
-    let position = map[index].iter().position(|(curr_key, _)| *curr_key == key);
+/// A single step of the initialization sequence.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Step<'a> {
+    /// `label-`: take the lens out of its box.
+    Remove(&'a str),
+    /// `label=focal`: put the lens in its box, or swap it for the one there.
+    Insert(&'a str, usize)
+}
 
-    if let Some(position) = position {
-        map[index].remove(position);
-        true
-    } else {
-        false
-    }
+#[derive(Debug, PartialEq)]
+pub enum StepError {
+    /// Step ending in neither `-` nor `=` and a focal length.
+    MissingOperation(String),
+    /// Label that is empty or not made only of letters.
+    InvalidLabel(String),
+    /// Focal length that is not a single digit from 1 to 9.
+    InvalidFocalLength(String)
 }
 
-fn upsert(input: &str, map: &mut OurMap) -> bool {
-    let (key, value) = input.split_once('=').unwrap();
-    let index = our_hash(key);
-    let position = map[index].iter().position(|(curr_key, _)| *curr_key == key);
+impl<'a> Step<'a> {
+    pub fn parse(step: &'a str) -> Result<Self, StepError> {
+        let (label, parsed) = if let Some(label) = step.strip_suffix('-') {
+            (label, Step::Remove(label))
+        } else if let Some((label, focal)) = step.split_once('=') {
+            let focal_len = match focal.parse() {
+                Ok(focal_len @ 1..=9) if focal.len() == 1 => focal_len,
+                _ => return Err(StepError::InvalidFocalLength(step.to_string()))
+            };
+            (label, Step::Insert(label, focal_len))
+        } else {
+            return Err(StepError::MissingOperation(step.to_string()))
+        };
+
+        if label.is_empty() || !label.chars().all(|character| character.is_ascii_alphabetic()) {
+            return Err(StepError::InvalidLabel(step.to_string()))
+        }
 
-    if let Some(position) = position {
-        map[index][position] = (key.into(), value.parse().unwrap());
-        true
-    } else {
-        map[index].push((key.into(), value.parse().unwrap()));
-        false
+        Ok(parsed)
+    }
+
+    pub fn label(&self) -> &'a str {
+        match self {
+            Step::Remove(label) | Step::Insert(label, _) => label
+        }
     }
 }
 
+impl Display for Step<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Remove(label) => write!(f, "{label}-"),
+            Step::Insert(label, focal_len) => write!(f, "{label}={focal_len}")
+        }
+    }
+}
 
-#[aoc(day15, part2)]
-fn part_two(input: &str) -> usize {
-    let mut map = vec![Vec::<(String, usize)>::new(); 256];
+/// Parses the comma separated steps, ignoring the newlines around them.
+pub fn parse_steps(input: &str) -> Result<Vec<Step<'_>>, StepError> {
     input
         .split(',')
-        .for_each(|element|
-            if element.contains('-') {
-                delete(&element.replace('-', ""), &mut map);
-            } else {
-                upsert(element, &mut map);
+        .map(|step| Step::parse(step.trim_matches(['\n', '\r'])))
+        .collect()
+}
+
+/// Builds the hashers that pick a box for each label, out of a fixed number
+/// of boxes.
+pub trait BoxHasher: BuildHasher {
+    fn box_count(&self) -> usize;
+
+    fn box_index(&self, label: &str) -> usize {
+        let mut hasher = self.build_hasher();
+        hasher.write(label.as_bytes());
+        hasher.finish() as usize % self.box_count()
+    }
+}
+
+/// The puzzle's HASH: add each byte, multiply and take the remainder. The
+/// puzzle uses a multiplier of 17 and a modulus of 256, one box per value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Holiday {
+    multiplier: u64,
+    modulus: u64
+}
+
+impl Holiday {
+    /// `None` if the modulus is zero, as there would be no boxes.
+    pub fn new(multiplier: u64, modulus: u64) -> Option<Self> {
+        (modulus > 0).then_some(Holiday { multiplier, modulus })
+    }
+}
+
+impl Default for Holiday {
+    fn default() -> Self {
+        Holiday { multiplier: 17, modulus: 256 }
+    }
+}
+
+pub struct HolidayHasher {
+    hash: u64,
+    multiplier: u64,
+    modulus: u64
+}
+
+impl Hasher for HolidayHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            // Widened so large multipliers and moduli cannot overflow.
+            let hash = (self.hash as u128 + *byte as u128) * self.multiplier as u128 % self.modulus as u128;
+            self.hash = hash as u64;
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl BuildHasher for Holiday {
+    type Hasher = HolidayHasher;
+
+    fn build_hasher(&self) -> HolidayHasher {
+        HolidayHasher { hash: 0, multiplier: self.multiplier, modulus: self.modulus }
+    }
+}
+
+impl BoxHasher for Holiday {
+    fn box_count(&self) -> usize {
+        self.modulus as usize
+    }
+}
+
+/// 64 bit FNV-1a, folded into `boxes` boxes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fnv {
+    boxes: usize
+}
+
+impl Fnv {
+    /// `None` if there are no boxes.
+    pub fn new(boxes: usize) -> Option<Self> {
+        (boxes > 0).then_some(Fnv { boxes })
+    }
+}
+
+pub struct FnvHasher(u64);
+
+impl Hasher for FnvHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl BuildHasher for Fnv {
+    type Hasher = FnvHasher;
+
+    fn build_hasher(&self) -> FnvHasher {
+        FnvHasher(0xcbf29ce484222325)
+    }
+}
+
+impl BoxHasher for Fnv {
+    fn box_count(&self) -> usize {
+        self.boxes
+    }
+}
+
+/// The HASHMAP from the puzzle: boxes picked by HASH, each holding its
+/// entries in the order they were first inserted. Other ways of picking boxes
+/// can be swapped in through `S`. Only boxes holding entries are stored, so
+/// the hasher may use as many boxes as it likes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HolidayHashMap<K, V, S = Holiday> {
+    boxes: BTreeMap<usize, Vec<(K, V)>>,
+    len: usize,
+    hash_builder: S
+}
+
+/// How evenly a map spreads its entries over its boxes.
+#[derive(Debug, PartialEq)]
+pub struct BoxStats {
+    /// Number of boxes holding each number of entries, from zero up.
+    pub occupancy: Vec<usize>,
+    pub longest_chain: usize,
+    /// Share of entries that landed in a box already holding another entry.
+    pub collision_rate: f64
+}
+
+/// A slot in the map that may or may not hold a value yet.
+pub struct Entry<'a, K, V> {
+    boxes: &'a mut BTreeMap<usize, Vec<(K, V)>>,
+    index: usize,
+    position: Option<usize>,
+    key: K,
+    len: &'a mut usize
+}
+
+impl<'a, K, V> Entry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'a mut V {
+        let lens_box = self.boxes.entry(self.index).or_default();
+        let position = match self.position {
+            Some(position) => position,
+            None => {
+                lens_box.push((self.key, default()));
+                *self.len += 1;
+                lens_box.len() - 1
             }
-        );
-
-    map
-        .iter()
-        .enumerate()
-        .map(|(box_num, ele)|
-            ele
-                .iter()
-                .enumerate()
-                .map(|(slot_num, (_, focal_len))|
-                    (box_num+1) * (slot_num+1) * focal_len
-                )
-                .sum::<usize>()
-        )
-        .sum()
+        };
+
+        &mut lens_box[position].1
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_default(self) -> &'a mut V where V: Default {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify(self, modify: impl FnOnce(&mut V)) -> Self {
+        if let Some(position) = self.position {
+            modify(&mut self.boxes.get_mut(&self.index).unwrap()[position].1);
+        }
+
+        self
+    }
+}
+
+impl<K: AsRef<str> + Eq, V, S: BoxHasher + Default> Default for HolidayHashMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K: AsRef<str> + Eq, V> HolidayHashMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_hasher(Holiday::default())
+    }
+}
+
+impl<K: AsRef<str> + Eq, V, S: BoxHasher> HolidayHashMap<K, V, S> {
+    pub fn with_hasher(hash_builder: S) -> Self {
+        HolidayHashMap {
+            boxes: BTreeMap::new(),
+            len: 0,
+            hash_builder
+        }
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn position<Q>(&self, key: &Q) -> (usize, Option<usize>)
+    where
+        K: Borrow<Q>,
+        Q: AsRef<str> + Eq + ?Sized
+    {
+        let index = self.hash_builder.box_index(key.as_ref());
+        let position = self.boxes
+            .get(&index)
+            .and_then(|lens_box| lens_box.iter().position(|(curr_key, _)| curr_key.borrow() == key));
+
+        (index, position)
+    }
+
+    /// Replaces the value in place if the key is already present, keeping its
+    /// slot, otherwise adds it to the back of its box.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let (index, position) = self.position(&key);
+        match position {
+            Some(position) => Some(std::mem::replace(&mut self.boxes.get_mut(&index).unwrap()[position].1, value)),
+            None => {
+                self.boxes.entry(index).or_default().push((key, value));
+                self.len += 1;
+                None
+            }
+        }
+    }
+
+    /// Removes the key, moving the entries behind it in its box forward and
+    /// dropping the box if it empties.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: AsRef<str> + Eq + ?Sized
+    {
+        let (index, position) = self.position(key);
+        let lens_box = self.boxes.get_mut(&index)?;
+        let (_, value) = lens_box.remove(position?);
+        if lens_box.is_empty() {
+            self.boxes.remove(&index);
+        }
+        self.len -= 1;
+
+        Some(value)
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: AsRef<str> + Eq + ?Sized
+    {
+        let (index, position) = self.position(key);
+        Some(&self.boxes.get(&index)?[position?].1)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: AsRef<str> + Eq + ?Sized
+    {
+        let (index, position) = self.position(key);
+        Some(&mut self.boxes.get_mut(&index)?[position?].1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: AsRef<str> + Eq + ?Sized
+    {
+        self.position(key).1.is_some()
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        let (index, position) = self.position(&key);
+        Entry { boxes: &mut self.boxes, index, position, key, len: &mut self.len }
+    }
+
+    /// Every non-empty box by number, with its entries in insertion order.
+    pub fn boxes(&self) -> impl Iterator<Item = (usize, &[(K, V)])> {
+        self.boxes
+            .iter()
+            .map(|(box_num, lens_box)| (*box_num, lens_box.as_slice()))
+    }
+
+    /// Entries box by box, each box in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.boxes
+            .values()
+            .flatten()
+            .map(|(key, value)| (key, value))
+    }
+
+    pub fn stats(&self) -> BoxStats {
+        let longest_chain = self.boxes.values().map(Vec::len).max().unwrap_or(0);
+        let mut occupancy = vec![0; longest_chain + 1];
+        for lens_box in self.boxes.values() {
+            occupancy[lens_box.len()] += 1;
+        }
+
+        let used_boxes = self.boxes.len();
+        occupancy[0] = self.hash_builder.box_count() - used_boxes;
+        let collision_rate = if self.len == 0 {
+            0.0
+        } else {
+            (self.len - used_boxes) as f64 / self.len as f64
+        };
+
+        BoxStats { occupancy, longest_chain, collision_rate }
+    }
+}
+
+impl<K: AsRef<str> + Eq, V: Display, S: BoxHasher> HolidayHashMap<K, V, S> {
+    /// The non-empty boxes, one per line, as the puzzle prints them:
+    /// `Box 3: [ot 7] [ab 5]`.
+    pub fn render(&self) -> String {
+        self.boxes()
+            .map(|(box_num, lens_box)| {
+                let lenses = lens_box
+                    .iter()
+                    .map(|(key, value)| format!("[{} {value}]", key.as_ref()))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                format!("Box {box_num}: {lenses}\n")
+            })
+            .collect()
+    }
+}
+
+impl<K: AsRef<str> + Eq, S: BoxHasher> HolidayHashMap<K, usize, S> {
+    /// Sum over every lens of its box number, slot number and focal length,
+    /// counting boxes and slots from one.
+    pub fn focusing_power(&self) -> usize {
+        self.boxes()
+            .map(|(box_num, lens_box)|
+                lens_box
+                    .iter()
+                    .enumerate()
+                    .map(|(slot_num, (_, focal_len))|
+                        (box_num+1) * (slot_num+1) * focal_len
+                    )
+                    .sum::<usize>()
+            )
+            .sum()
+    }
+}
+
+impl<K, V, S, Q> Index<&Q> for HolidayHashMap<K, V, S>
+where
+    K: AsRef<str> + Eq + Borrow<Q>,
+    Q: AsRef<str> + Eq + ?Sized,
+    S: BoxHasher
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("Key should be in the map")
+    }
+}
+
+impl<K, V, S> IntoIterator for HolidayHashMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = std::iter::Flatten<btree_map::IntoValues<usize, Vec<(K, V)>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.boxes.into_values().flatten()
+    }
+}
+
+impl<'a, K: AsRef<str> + Eq, V, S: BoxHasher> IntoIterator for &'a HolidayHashMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Box<dyn Iterator<Item = (&'a K, &'a V)> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+impl<K: AsRef<str> + Eq, V, S: BoxHasher> Extend<(K, V)> for HolidayHashMap<K, V, S> {
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<K: AsRef<str> + Eq, V, S: BoxHasher + Default> FromIterator<(K, V)> for HolidayHashMap<K, V, S> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut map = HolidayHashMap::default();
+        map.extend(iter);
+        map
+    }
+}
+
+fn apply_step<'a, S: BoxHasher>(step: &Step<'a>, map: &mut HolidayHashMap<&'a str, usize, S>) {
+    match *step {
+        Step::Remove(label) => { map.remove(label); },
+        Step::Insert(label, focal_len) => { map.insert(label, focal_len); }
+    }
+}
+
+/// Runs the steps through a map boxed by `hash_builder` and reports how the
+/// lenses ended up spread over the boxes.
+pub fn box_stats<S: BoxHasher>(steps: &[Step], hash_builder: S) -> BoxStats {
+    let mut map = HolidayHashMap::with_hasher(hash_builder);
+    steps.iter().for_each(|step| apply_step(step, &mut map));
+
+    map.stats()
+}
+
+/// Runs the steps, writing the boxes after each one the way the puzzle's
+/// walkthrough does, and returns the final map.
+pub fn replay<'a>(steps: &[Step<'a>], out: &mut impl Write) -> io::Result<HolidayHashMap<&'a str, usize>> {
+    let mut map = HolidayHashMap::new();
+    for (step_num, step) in steps.iter().enumerate() {
+        apply_step(step, &mut map);
+        if step_num > 0 {
+            writeln!(out)?;
+        }
+        write!(out, "After \"{step}\":\n{}", map.render())?;
+    }
+
+    Ok(map)
 }
 
+#[aoc(day15, part2)]
+fn part_two(input: &str) -> usize {
+    let steps = parse_steps(input).expect("Invalid initialization sequence");
+    let mut map = HolidayHashMap::new();
+    steps.iter().for_each(|step| apply_step(step, &mut map));
+
+    map.focusing_power()
+}
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+    use indoc::indoc;
     use super::*;
+    use crate::test_random::XorShift;
 
     #[test]
     fn part1_1() {
@@ -99,4 +554,162 @@ mod tests {
         let result = part_two(input);
         assert_eq!(result, 145);
     }
+
+    #[test]
+    fn holiday_hash_map_1() {
+        let map = [("rn", 1), ("cm", 2), ("ot", 9), ("ab", 5), ("pc", 6), ("ot", 7)]
+            .into_iter()
+            .collect::<HolidayHashMap<_, _>>();
+        let boxes = map
+            .boxes()
+            .map(|(box_num, lens_box)| (box_num, lens_box.to_vec()))
+            .collect::<Vec<_>>();
+        assert_eq!(boxes, vec![
+            (0, vec![("rn", 1), ("cm", 2)]),
+            (3, vec![("ot", 7), ("ab", 5), ("pc", 6)])
+        ]);
+        assert_eq!(map["ot"], 7);
+        assert_eq!(map.len(), 5);
+    }
+
+    #[test]
+    fn holiday_hash_map_2() {
+        let mut random = XorShift::new(15);
+        let keys = ["rn", "cm", "qp", "pc", "ot", "ab", "HASH", "a", "b", "xyz"];
+
+        let mut expected = HashMap::new();
+        let mut result = HolidayHashMap::new();
+        for _ in 0..1_000 {
+            let key = keys[random.below(keys.len() as u64) as usize].to_string();
+            let value = random.below(10) as usize;
+            match random.below(4) {
+                0 => assert_eq!(result.remove(key.as_str()), expected.remove(key.as_str())),
+                1 => {
+                    *result.entry(key.clone()).and_modify(|curr| *curr += value).or_insert(value) += 1;
+                    *expected.entry(key).and_modify(|curr| *curr += value).or_insert(value) += 1;
+                },
+                _ => assert_eq!(result.insert(key.clone(), value), expected.insert(key, value))
+            }
+
+            assert_eq!(result.len(), expected.len());
+            for key in keys {
+                assert_eq!(result.get(key), expected.get(key));
+                assert_eq!(result.contains_key(key), expected.contains_key(key));
+            }
+        }
+
+        let mut entries = result.into_iter().collect::<Vec<_>>();
+        let mut expected_entries = expected.into_iter().collect::<Vec<_>>();
+        entries.sort();
+        expected_entries.sort();
+        assert_eq!(entries, expected_entries);
+    }
+
+    #[test]
+    fn holiday_hash_map_3() {
+        let mut map = HolidayHashMap::with_hasher(Holiday::new(17, 1 << 32).unwrap());
+        map.extend([("rn", 1), ("cm", 2), ("ot", 7)]);
+        *map.entry("qp").or_default() += 3;
+        assert_eq!(map.remove("cm"), Some(2));
+        assert_eq!(map.boxes().count(), 3);
+        assert_eq!(map.stats().occupancy, vec![(1 << 32) - 3, 3]);
+        assert_eq!(map["qp"], 3);
+
+        let mut map = HolidayHashMap::with_hasher(Fnv::new(usize::MAX).unwrap());
+        map.insert("a", 1);
+        assert_eq!(map.boxes().map(|(box_num, _)| box_num).collect::<Vec<_>>(), vec![0xaf63dc4c8601ec8c]);
+    }
+
+    #[test]
+    fn hashers_1() {
+        assert_eq!(Holiday::default().box_index("HASH"), our_hash("HASH"));
+        assert_eq!(Holiday::new(31, 64).unwrap().box_index("HASH"), 50);
+        assert_eq!(Holiday::new(u64::MAX, u64::MAX - 1).unwrap().box_index("HASH"), 292);
+        assert_eq!(Fnv::new(usize::MAX).unwrap().box_index("a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(Holiday::new(17, 0), None);
+        assert_eq!(Fnv::new(0), None);
+
+        let mut map = HolidayHashMap::with_hasher(Fnv::new(16).unwrap());
+        map.extend([("rn", 1), ("cm", 2)]);
+        assert_eq!(map.boxes().count(), 2);
+        assert_eq!(map["cm"], 2);
+    }
+
+    #[test]
+    fn box_stats_1() {
+        let input = "rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7";
+        let steps = parse_steps(input).unwrap();
+        let result = box_stats(&steps, Holiday::default());
+        let mut occupancy = vec![0; 4];
+        occupancy[0] = 254;
+        occupancy[2] = 1;
+        occupancy[3] = 1;
+        assert_eq!(result, BoxStats { occupancy, longest_chain: 3, collision_rate: 0.6 });
+
+        let result = box_stats(&steps, Fnv::new(256).unwrap());
+        assert_eq!(result.occupancy.iter().sum::<usize>(), 256);
+    }
+
+    #[test]
+    fn parse_steps_1() {
+        let steps = parse_steps("rn=1,cm-,qp=3\n").unwrap();
+        assert_eq!(steps, vec![Step::Insert("rn", 1), Step::Remove("cm"), Step::Insert("qp", 3)]);
+        assert_eq!(part_two("rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7\n"), 145);
+
+        assert_eq!(parse_steps("rn=1,cm"), Err(StepError::MissingOperation("cm".to_string())));
+        assert_eq!(parse_steps("r-n=1"), Err(StepError::InvalidLabel("r-n=1".to_string())));
+        assert_eq!(parse_steps("=1"), Err(StepError::InvalidLabel("=1".to_string())));
+        assert_eq!(parse_steps("rn=0"), Err(StepError::InvalidFocalLength("rn=0".to_string())));
+        assert_eq!(parse_steps("rn=12"), Err(StepError::InvalidFocalLength("rn=12".to_string())));
+    }
+
+    #[test]
+    fn replay_1() {
+        let steps = parse_steps("rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7").unwrap();
+        let mut out = Vec::new();
+        let map = replay(&steps, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), indoc! {r#"
+            After "rn=1":
+            Box 0: [rn 1]
+
+            After "cm-":
+            Box 0: [rn 1]
+
+            After "qp=3":
+            Box 0: [rn 1]
+            Box 1: [qp 3]
+
+            After "cm=2":
+            Box 0: [rn 1] [cm 2]
+            Box 1: [qp 3]
+
+            After "qp-":
+            Box 0: [rn 1] [cm 2]
+
+            After "pc=4":
+            Box 0: [rn 1] [cm 2]
+            Box 3: [pc 4]
+
+            After "ot=9":
+            Box 0: [rn 1] [cm 2]
+            Box 3: [pc 4] [ot 9]
+
+            After "ab=5":
+            Box 0: [rn 1] [cm 2]
+            Box 3: [pc 4] [ot 9] [ab 5]
+
+            After "pc-":
+            Box 0: [rn 1] [cm 2]
+            Box 3: [ot 9] [ab 5]
+
+            After "pc=6":
+            Box 0: [rn 1] [cm 2]
+            Box 3: [ot 9] [ab 5] [pc 6]
+
+            After "ot=7":
+            Box 0: [rn 1] [cm 2]
+            Box 3: [ot 7] [ab 5] [pc 6]
+        "#});
+        assert_eq!(map.focusing_power(), 145);
+    }
 }
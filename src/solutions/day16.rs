@@ -1,17 +1,19 @@
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+use itertools::Itertools;
+use pathfinding::directed::strongly_connected_components::strongly_connected_components;
 use rayon::prelude::*;
 
-type Grid = Vec<Vec<Tile>>;
+pub type Grid = Vec<Vec<Tile>>;
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
-enum Direction {
+pub enum Direction {
     Up,
     Down,
     Left,
     Right
 }
 
-enum Tile {
+pub enum Tile {
     LeftUp,
     RightUp,
     VerticalSplitter,
@@ -19,11 +21,11 @@ enum Tile {
     Empty
 }
 
-#[derive(Debug)]
-struct Movement {
-    x: usize,
-    y: usize,
-    direction: Direction
+#[derive(Clone, Debug)]
+pub struct Movement {
+    pub x: usize,
+    pub y: usize,
+    pub direction: Direction
 }
 
 enum MoveResult {
@@ -187,8 +189,7 @@ fn move_tile(movement: Movement, grid: &Grid) -> MoveResult {
     }
 }
 
-fn check_path(starting_spot: Movement, grid: &Grid) -> usize {
-    let mut tiles_visited: HashSet<(usize, usize)> = HashSet::new();
+fn beam_crossings(starting_spot: Movement, grid: &Grid) -> HashSet<(usize, usize, Direction)> {
     let mut dashes_done = HashSet::new();
 
     let mut tile_paths = VecDeque::new();
@@ -196,7 +197,6 @@ fn check_path(starting_spot: Movement, grid: &Grid) -> usize {
 
     while let Some(mut curr_move) = tile_paths.pop_front() {
         loop {
-            tiles_visited.insert((curr_move.x, curr_move.y));
             if !dashes_done.insert((curr_move.x, curr_move.y, curr_move.direction.clone())) {
                 break
             }
@@ -213,7 +213,116 @@ fn check_path(starting_spot: Movement, grid: &Grid) -> usize {
         }
     }
 
-    tiles_visited.len()
+    dashes_done
+}
+
+fn check_path(starting_spot: Movement, grid: &Grid) -> usize {
+    beam_crossings(starting_spot, grid)
+        .into_iter()
+        .map(|(x, y, _)| (x, y))
+        .unique()
+        .count()
+}
+
+/// Every tile energized by a beam entering at `starting_spot`, along with the
+/// directions beams crossed it in.
+pub fn energized_tiles(starting_spot: Movement, grid: &Grid) -> HashMap<(usize, usize), HashSet<Direction>> {
+    beam_crossings(starting_spot, grid)
+        .into_iter()
+        .fold(HashMap::new(), |mut tiles, (x, y, direction)| {
+            tiles.entry((x, y)).or_insert_with(HashSet::new).insert(direction);
+            tiles
+        })
+}
+
+/// Draws energized tiles as `#` and the rest as `.`, like the puzzle.
+pub fn render_energized(grid: &Grid, tiles: &HashMap<(usize, usize), HashSet<Direction>>) -> String {
+    (0..grid.len())
+        .map(|x|
+            (0..grid[0].len())
+                .map(|y| if tiles.contains_key(&(x, y)) { '#' } else { '.' })
+                .collect::<String>() + "\n"
+        )
+        .collect()
+}
+
+/// Draws the beams over the grid like the puzzle: empty tiles show the
+/// direction of the beam crossing them, or how many beams do if more than one.
+pub fn render_beams(grid: &Grid, tiles: &HashMap<(usize, usize), HashSet<Direction>>) -> String {
+    grid
+        .iter()
+        .enumerate()
+        .map(|(x, row)|
+            row
+                .iter()
+                .enumerate()
+                .map(|(y, tile)| {
+                    let directions = tiles.get(&(x, y)).map(HashSet::len).unwrap_or(0);
+                    match (tile, directions) {
+                        (Tile::LeftUp, _) => '/',
+                        (Tile::RightUp, _) => '\\',
+                        (Tile::VerticalSplitter, _) => '|',
+                        (Tile::HorizontalSplitter, _) => '-',
+                        (Tile::Empty, 0) => '.',
+                        (Tile::Empty, 1) => match tiles[&(x, y)].iter().next().unwrap() {
+                            Direction::Up => '^',
+                            Direction::Down => 'v',
+                            Direction::Left => '<',
+                            Direction::Right => '>'
+                        },
+                        (Tile::Empty, count) => char::from_digit(count as u32, 10).unwrap()
+                    }
+                })
+                .collect::<String>() + "\n"
+        )
+        .collect()
+}
+
+/// How many of the part 2 entry points energize each tile.
+pub fn energized_counts(grid: &Grid) -> Vec<Vec<usize>> {
+    edge_movements(grid)
+        .into_par_iter()
+        .map(|movement| energized_tiles(movement, grid))
+        .fold(
+            || vec![vec![0; grid[0].len()]; grid.len()],
+            |mut counts, tiles| {
+                for (x, y) in tiles.into_keys() {
+                    counts[x][y] += 1;
+                }
+                counts
+            }
+        )
+        .reduce(
+            || vec![vec![0; grid[0].len()]; grid.len()],
+            |mut counts, other| {
+                for (count, other_count) in counts.iter_mut().flatten().zip(other.into_iter().flatten()) {
+                    *count += other_count;
+                }
+                counts
+            }
+        )
+}
+
+/// Renders counts as a plain PPM image, one pixel per tile, running from
+/// black through red and yellow to white for the most energized tiles.
+pub fn heatmap_ppm(counts: &[Vec<usize>]) -> String {
+    let max_count = counts.iter().flatten().copied().max().unwrap_or(0).max(1);
+    let channel = |heat: usize, offset: usize| (heat.saturating_sub(offset * 255)).min(255);
+
+    let mut ppm = format!("P3\n{} {}\n255\n", counts.first().map(Vec::len).unwrap_or(0), counts.len());
+    for row in counts {
+        let pixels = row
+            .iter()
+            .map(|count| {
+                let heat = count * 3 * 255 / max_count;
+                format!("{} {} {}", channel(heat, 0), channel(heat, 1), channel(heat, 2))
+            })
+            .join(" ");
+        ppm.push_str(&pixels);
+        ppm.push('\n');
+    }
+
+    ppm
 }
 
 #[aoc(day16, part1)]
@@ -221,7 +330,179 @@ fn part_one(grid: &Grid) -> usize {
     check_path(Movement { x: 0, y: 0, direction: Direction::Right }, grid)
 }
 
-#[aoc(day16, part2)]
+#[derive(Clone, Debug, PartialEq)]
+struct Bitset(Vec<u64>);
+
+impl Bitset {
+    fn new(len: usize) -> Self {
+        Bitset(vec![0; len.div_ceil(64)])
+    }
+
+    fn insert(&mut self, index: usize) {
+        self.0[index / 64] |= 1 << (index % 64);
+    }
+
+    fn union_with(&mut self, other: &Bitset) {
+        for (block, other_block) in self.0.iter_mut().zip(other.0.iter()) {
+            *block |= other_block;
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.0.iter().map(|block| block.count_ones() as usize).sum()
+    }
+}
+
+fn splits(movement: &Movement, grid: &Grid) -> bool {
+    matches!(
+        (&movement.direction, &grid[movement.x][movement.y]),
+        (Direction::Up | Direction::Down, Tile::HorizontalSplitter) |
+        (Direction::Left | Direction::Right, Tile::VerticalSplitter)
+    )
+}
+
+/// Beam paths between splitters. A beam split by a splitter leaves it the same
+/// way whichever side it hit, so each splitter is a node whose edges are the
+/// beam segments it sends out, ending at the next splitter they split on or
+/// off the grid. Splitters that feed each other are collapsed into one
+/// component and every component stores the cells energized from it, so any
+/// entry point only has to trace its beam to the first splitter.
+struct BeamGraph<'a> {
+    grid: &'a Grid,
+    components: HashMap<(usize, usize), usize>,
+    energized: Vec<Bitset>
+}
+
+impl<'a> BeamGraph<'a> {
+    fn new(grid: &'a Grid) -> Self {
+        let splitters = grid
+            .iter()
+            .enumerate()
+            .flat_map(|(x, row)|
+                row
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, tile)| matches!(tile, Tile::VerticalSplitter | Tile::HorizontalSplitter))
+                    .map(move |(y, _)| (x, y))
+            )
+            .collect_vec();
+
+        let mut graph = BeamGraph { grid, components: HashMap::new(), energized: Vec::new() };
+        let segments = splitters
+            .iter()
+            .map(|&(x, y)| {
+                let direction = match grid[x][y] {
+                    Tile::VerticalSplitter => Direction::Right,
+                    _ => Direction::Down
+                };
+                let mut cells = graph.empty_cells();
+                cells.insert(graph.cell_index(x, y));
+                let mut next_splitters = Vec::new();
+                let outgoing = match move_tile(Movement { x, y, direction }, grid) {
+                    MoveResult::OutOfBounds => vec![],
+                    MoveResult::NextMove(movement) => vec![movement],
+                    MoveResult::Split(movement_1, movement_2) => vec![movement_1, movement_2]
+                };
+                for movement in outgoing {
+                    let (segment, next_splitter) = graph.trace_segment(movement);
+                    cells.union_with(&segment);
+                    next_splitters.extend(next_splitter);
+                }
+                ((x, y), (cells, next_splitters))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let condensed = strongly_connected_components(&splitters, |splitter| segments[splitter].1.clone());
+        for (i, component) in condensed.iter().enumerate() {
+            for splitter in component {
+                graph.components.insert(*splitter, i);
+            }
+        }
+
+        // Components come out after every component they lead to, so those
+        // are always filled in first
+        for (i, component) in condensed.iter().enumerate() {
+            let mut cells = graph.empty_cells();
+            for splitter in component {
+                let (own_cells, next_splitters) = &segments[splitter];
+                cells.union_with(own_cells);
+                for next_splitter in next_splitters {
+                    let next_component = graph.components[next_splitter];
+                    if next_component != i {
+                        cells.union_with(&graph.energized[next_component]);
+                    }
+                }
+            }
+            graph.energized.push(cells);
+        }
+
+        graph
+    }
+
+    fn empty_cells(&self) -> Bitset {
+        Bitset::new(self.grid.len() * self.grid[0].len())
+    }
+
+    fn cell_index(&self, x: usize, y: usize) -> usize {
+        x * self.grid[0].len() + y
+    }
+
+    /// Follows a beam until it splits or leaves the grid, returning the cells
+    /// it crossed and the splitter it ended on.
+    fn trace_segment(&self, mut curr_move: Movement) -> (Bitset, Option<(usize, usize)>) {
+        let mut cells = self.empty_cells();
+        let mut dashes_done = HashSet::new();
+        loop {
+            cells.insert(self.cell_index(curr_move.x, curr_move.y));
+            if splits(&curr_move, self.grid) {
+                return (cells, Some((curr_move.x, curr_move.y)))
+            }
+            if !dashes_done.insert((curr_move.x, curr_move.y, curr_move.direction.clone())) {
+                return (cells, None)
+            }
+            curr_move = match move_tile(curr_move, self.grid) {
+                MoveResult::NextMove(movement) => movement,
+                _ => return (cells, None)
+            };
+        }
+    }
+
+    fn energized(&self, starting_spot: Movement) -> usize {
+        let (mut cells, splitter) = self.trace_segment(starting_spot);
+        if let Some(splitter) = splitter {
+            cells.union_with(&self.energized[self.components[&splitter]]);
+        }
+        cells.len()
+    }
+}
+
+fn edge_movements(grid: &Grid) -> Vec<Movement> {
+    let (len_x, len_y) = (grid.len(), grid[0].len());
+
+    (0..len_x)
+        .flat_map(|i| [
+            Movement { x: i, y: 0, direction: Direction::Right },
+            Movement { x: i, y: len_y-1, direction: Direction::Left }
+        ])
+        .chain((0..len_y).flat_map(|i| [
+            Movement { x: 0, y: i, direction: Direction::Down },
+            Movement { x: len_x-1, y: i, direction: Direction::Up }
+        ]))
+        .collect()
+}
+
+#[aoc(day16, part2, BeamGraph)]
+fn part_two_beam_graph(grid: &Grid) -> usize {
+    let graph = BeamGraph::new(grid);
+
+    edge_movements(grid)
+        .into_iter()
+        .map(|movement| graph.energized(movement))
+        .max()
+        .unwrap()
+}
+
+#[aoc(day16, part2, Rayon)]
 fn part_two(grid: &Grid) -> usize {
     let (len_x, len_y) = (grid.len(), grid[0].len());
 
@@ -298,4 +579,87 @@ mod tests {
         let result = part_two(&input_generator(input));
         assert_eq!(result, 51);
     }
+
+    #[test]
+    fn part2_2() {
+        let input = indoc! {r#"
+            .|...\....
+            |.-.\.....
+            .....|-...
+            ........|.
+            ..........
+            .........\
+            ..../.\\..
+            .-.-/..|..
+            .|....-|.\
+            ..//.|....
+        "#};
+        let grid = input_generator(input);
+        let graph = BeamGraph::new(&grid);
+        for movement in edge_movements(&grid) {
+            let expected = check_path(movement.clone(), &grid);
+            assert_eq!(graph.energized(movement), expected);
+        }
+        assert_eq!(part_two_beam_graph(&grid), 51);
+    }
+
+    #[test]
+    fn render_1() {
+        let input = indoc! {r#"
+            .|...\....
+            |.-.\.....
+            .....|-...
+            ........|.
+            ..........
+            .........\
+            ..../.\\..
+            .-.-/..|..
+            .|....-|.\
+            ..//.|....
+        "#};
+        let grid = input_generator(input);
+        let tiles = energized_tiles(Movement { x: 0, y: 0, direction: Direction::Right }, &grid);
+        assert_eq!(render_energized(&grid, &tiles), indoc! {"
+            ######....
+            .#...#....
+            .#...#####
+            .#...##...
+            .#...##...
+            .#...##...
+            .#..####..
+            ########..
+            .#######..
+            .#...#.#..
+        "});
+        assert_eq!(render_beams(&grid, &tiles), indoc! {r#"
+            >|<<<\....
+            |v-.\^....
+            .v...|->>>
+            .v...v^.|.
+            .v...v^...
+            .v...v^..\
+            .v../2\\..
+            <->-/vv|..
+            .|<<<2-|.\
+            .v//.|.v..
+        "#});
+    }
+
+    #[test]
+    fn heatmap_1() {
+        let input = indoc! {r#"
+            .|
+            ..
+        "#};
+        let grid = input_generator(input);
+        let counts = energized_counts(&grid);
+        assert_eq!(counts, vec![vec![3, 4], vec![4, 6]]);
+        assert_eq!(heatmap_ppm(&counts), indoc! {"
+            P3
+            2 2
+            255
+            255 127 0 255 255 0
+            255 255 0 255 255 255
+        "});
+    }
 }
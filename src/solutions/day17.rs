@@ -1,13 +1,14 @@
+use std::cell::Cell;
 use itertools::Itertools;
-use pathfinding::prelude::{dijkstra, astar};
+use pathfinding::prelude::{dijkstra, dijkstra_all, astar};
 
-type Grid = Vec<Vec<u32>>;
-type Coords = (usize, usize);
-type DirectionDuration = (u32, Direction);
-type State = (Coords, DirectionDuration);
+pub type Grid = Vec<Vec<u32>>;
+pub type Coords = (usize, usize);
+pub type DirectionDuration = (u32, Direction);
+pub type State = (Coords, DirectionDuration);
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
-enum Direction {
+pub enum Direction {
     Up,
     Down,
     Left,
@@ -22,6 +23,15 @@ impl Direction {
         Direction::Right
     ];
 
+    fn arrow(&self) -> char {
+        match self {
+            Direction::Up => '^',
+            Direction::Down => 'v',
+            Direction::Left => '<',
+            Direction::Right => '>'
+        }
+    }
+
     fn complement(&self) -> Direction {
         match self {
             Direction::Up => Direction::Down,
@@ -78,190 +88,281 @@ fn next_pos(
     }
 }
 
-#[aoc(day17, part1, Dijkstra)]
-fn part_one(grid: &Grid) -> u32 {
-    let (x_len, y_len) = (grid.len(), grid[0].len());
+/// How far a crucible must and may travel in a straight line, counted in
+/// cells, and whether it may turn straight back the way it came.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Crucible {
+    pub min_run: u32,
+    pub max_run: u32,
+    pub can_reverse: bool
+}
 
-    let shortest_path: (Vec<State>, u32) = dijkstra(
-        &((0, 0), (0, Direction::Right)),
-        |((curr_x, curr_y), (prev_duration, prev_direction))| {
-            let complement = prev_direction.complement();
-            Direction::ALL_DIRECTIONS
+/// Where a crucible travels: the heat lost entering each cell, any extra heat
+/// lost by turning within a cell, and the cells it may leave from and stop at.
+#[derive(Debug, Clone)]
+pub struct City<'a> {
+    pub heat_loss: &'a Grid,
+    pub turn_penalties: Option<&'a Grid>,
+    pub starts: Vec<Coords>,
+    pub goals: Vec<Coords>
+}
+
+impl<'a> City<'a> {
+    /// Travel from the top left corner to the bottom right, as in the puzzle.
+    pub fn new(heat_loss: &'a Grid) -> Self {
+        City {
+            heat_loss,
+            turn_penalties: None,
+            starts: vec![(0, 0)],
+            goals: vec![(heat_loss.len() - 1, heat_loss[0].len() - 1)]
+        }
+    }
+}
+
+impl Crucible {
+    pub const REGULAR: Crucible = Crucible { min_run: 1, max_run: 3, can_reverse: false };
+    pub const ULTRA: Crucible = Crucible { min_run: 4, max_run: 10, can_reverse: false };
+
+    /// Moves available from `state` with the heat each one loses. A run length
+    /// of zero means the crucible is at rest and may head in any direction.
+    pub fn successors(&self, city: &City, ((curr_x, curr_y), (prev_duration, prev_direction)): &State) -> Vec<(State, u32)> {
+        let complement = prev_direction.complement();
+        Direction::ALL_DIRECTIONS
+            .iter()
+            // Remove unavailable directions
+            .filter(|pos_direction| {
+                if *prev_duration == 0 {
+                    true
+                } else if *pos_direction == prev_direction {
+                    *prev_duration < self.max_run
+                } else {
+                    *prev_duration >= self.min_run && (self.can_reverse || **pos_direction != complement)
+                }
+            })
+            // Convert directions to coords and validate bounds
+            .filter_map(|pos_direction|
+                next_pos(pos_direction, (curr_x, curr_y), city.heat_loss)
+                    .map(|coords| (coords, pos_direction))
+            )
+            // Add direction duration tracking
+            .map(|(pos_coords, pos_direction)| {
+                let heat_loss = city.heat_loss[pos_coords.0][pos_coords.1];
+                if pos_direction == prev_direction {
+                    ((pos_coords, (prev_duration + 1, prev_direction.clone())), heat_loss)
+                } else {
+                    let turn_penalty = match (prev_duration, city.turn_penalties) {
+                        (1.., Some(penalties)) => penalties[*curr_x][*curr_y],
+                        _ => 0
+                    };
+                    ((pos_coords, (1, pos_direction.clone())), heat_loss + turn_penalty)
+                }
+            })
+            .collect_vec()
+    }
+
+    pub fn is_goal(&self, city: &City, (position, (prev_duration, _)): &State) -> bool {
+        city.goals.contains(position) && (*prev_duration == 0 || *prev_duration >= self.min_run)
+    }
+
+    // Searches start from a virtual `None` state joined to every start cell
+    fn start_successors(&self, city: &City, state: &Option<State>, expanded: &Cell<usize>) -> Vec<(Option<State>, u32)> {
+        expanded.set(expanded.get() + 1);
+        match state {
+            None => city.starts
                 .iter()
-                // Remove unavailable directions
-                .filter(|pos_direction| {
-                    if **pos_direction == complement {
-                        false
-                    } else if *prev_duration == 3 {
-                        *pos_direction != prev_direction
-                    } else {
-                        true
-                    }
-                })
-                // Convert directions to coords and validate bounds
-                .filter_map(|pos_direction|
-                    next_pos(pos_direction, (curr_x, curr_y), grid)
-                        .map(|coords| (coords, pos_direction))
-                )
-                // Add direction duration tracking
-                .map(|(pos_coords, pos_direction)| {
-                    if pos_direction == prev_direction {
-                        ((pos_coords, (prev_duration+1, prev_direction.clone())), grid[pos_coords.0][pos_coords.1])
-                    } else {
-                        ((pos_coords, (1, pos_direction.clone())), grid[pos_coords.0][pos_coords.1])
-                    }
-                })
-                .collect_vec()
-        },
-        |(position, _)| {
-            *position == (x_len-1, y_len-1)
+                .map(|start| (Some((*start, (0, Direction::Right))), 0))
+                .collect(),
+            Some(state) => self.successors(city, state)
+                .into_iter()
+                .map(|(next, heat_loss)| (Some(next), heat_loss))
+                .collect()
         }
-    )
-    .expect("Should have valid path");
+    }
 
-    shortest_path.1
-}
+    /// Least heat loss path from any start to any goal, as every state along
+    /// the way (starting at rest on a start cell) and the total heat loss.
+    pub fn dijkstra(&self, city: &City) -> Option<(Vec<State>, u32)> {
+        self.dijkstra_counted(city).0
+    }
 
-#[aoc(day17, part1, Astar)]
-fn part_one_astar(grid: &Grid) -> u32 {
-    let (x_len, y_len) = (grid.len(), grid[0].len());
+    /// [`Crucible::dijkstra`] along with the number of states it expanded.
+    pub fn dijkstra_counted(&self, city: &City) -> (Option<(Vec<State>, u32)>, usize) {
+        let expanded = Cell::new(0);
+        let shortest_path = dijkstra(
+            &None,
+            |state| self.start_successors(city, state, &expanded),
+            |state| state.as_ref().is_some_and(|state| self.is_goal(city, state))
+        )
+        .map(|(path, heat_loss)| (path.into_iter().flatten().collect(), heat_loss));
 
-    let shortest_path: (Vec<State>, u32) = astar(
-        &((0, 0), (0, Direction::Right)),
-        |((curr_x, curr_y), (prev_duration, prev_direction))| {
-            let complement = prev_direction.complement();
-            Direction::ALL_DIRECTIONS
+        (shortest_path, expanded.get())
+    }
+
+    /// Same as [`Crucible::dijkstra`], guided by the manhattan distance to the
+    /// nearest goal.
+    pub fn astar(&self, city: &City) -> Option<(Vec<State>, u32)> {
+        self.astar_by(city, |(curr_x, curr_y)| {
+            city.goals
                 .iter()
-                .filter(|pos_direction| {
-                    if **pos_direction == complement {
-                        false
-                    } else if *prev_duration == 3 {
-                        *pos_direction != prev_direction
-                    } else {
-                        true
-                    }
-                })
-                .filter_map(|pos_direction|
-                    next_pos(pos_direction, (curr_x, curr_y), grid)
-                        .map(|coords| (coords, pos_direction))
-                )
-                .map(|(pos_coords, pos_direction)| {
-                    if pos_direction == prev_direction {
-                        ((pos_coords, (prev_duration+1, prev_direction.clone())), grid[pos_coords.0][pos_coords.1])
-                    } else {
-                        ((pos_coords, (1, pos_direction.clone())), grid[pos_coords.0][pos_coords.1])
-                    }
-                })
-                .collect_vec()
-        },
-        |((curr_x, curr_y), _)| {
-            let (target_x, target_y) = (x_len-1, y_len-1);
-            ((target_x - curr_x) + (target_y - curr_y)) as u32
-        },
-        |(position, _)| {
-            *position == (x_len-1, y_len-1)
+                .map(|(target_x, target_y)| (curr_x.abs_diff(*target_x) + curr_y.abs_diff(*target_y)) as u32)
+                .min()
+                .unwrap_or(0)
+        })
+        .0
+    }
+
+    /// Same as [`Crucible::dijkstra`], guided by [`heat_to_goal`].
+    pub fn astar_lower_bound(&self, city: &City) -> Option<(Vec<State>, u32)> {
+        self.astar_lower_bound_counted(city).0
+    }
+
+    /// [`Crucible::astar_lower_bound`] along with the number of states it expanded.
+    pub fn astar_lower_bound_counted(&self, city: &City) -> (Option<(Vec<State>, u32)>, usize) {
+        if city.goals.is_empty() {
+            return (None, 0)
         }
-    )
-    .expect("Should have valid path");
+        let lower_bound = heat_to_goal(city);
+        self.astar_by(city, |(x, y)| lower_bound[x][y])
+    }
 
-    shortest_path.1
+    fn astar_by(&self, city: &City, heuristic: impl Fn(Coords) -> u32) -> (Option<(Vec<State>, u32)>, usize) {
+        let expanded = Cell::new(0);
+        let shortest_path = astar(
+            &None,
+            |state| self.start_successors(city, state, &expanded),
+            |state| match state {
+                Some((position, _)) => heuristic(*position),
+                None => city.starts.iter().map(|start| heuristic(*start)).min().unwrap_or(0)
+            },
+            |state| state.as_ref().is_some_and(|state| self.is_goal(city, state))
+        )
+        .map(|(path, heat_loss)| (path.into_iter().flatten().collect(), heat_loss));
+
+        (shortest_path, expanded.get())
+    }
 }
 
-#[aoc(day17, part2, Dijkstra)]
-fn part_two(grid: &Grid) -> u32 {
-    let (x_len, y_len) = (grid.len(), grid[0].len());
+/// Least heat lost getting from each cell to the nearest goal when the
+/// crucible may steer however it likes, found by running Dijkstra backwards
+/// from the goals. Every real crucible path is also a path here, and turn
+/// penalties only add heat, so this never overestimates the heat still to be
+/// lost and A* guided by it always finds the least heat loss path. Cells that
+/// cannot reach a goal are `u32::MAX`.
+pub fn heat_to_goal(city: &City) -> Vec<Vec<u32>> {
+    let heat_loss = city.heat_loss;
+    let reached = dijkstra_all(&None, |cell: &Option<Coords>| match cell {
+        None => city.goals.iter().map(|goal| (Some(*goal), 0)).collect_vec(),
+        Some((x, y)) => Direction::ALL_DIRECTIONS
+            .iter()
+            .filter_map(|direction| next_pos(direction, (x, y), heat_loss))
+            .map(|prev| (Some(prev), heat_loss[*x][*y]))
+            .collect_vec()
+    });
 
-    let shortest_path: (Vec<State>, u32) = dijkstra(
-        &((0, 0), (0, Direction::Right)),
-        |((curr_x, curr_y), (prev_duration, prev_direction))| {
-            let complement = prev_direction.complement();
-            Direction::ALL_DIRECTIONS
-                .iter()
-                .filter(|pos_direction| {
-                    if **pos_direction == complement {
-                        false
-                    } else if *prev_duration < 4 {
-                        *pos_direction == prev_direction
-                    } else if *prev_duration == 10 {
-                        *pos_direction != prev_direction
-                    } else {
-                        true
-                    }
-                })
-                .filter_map(|pos_direction|
-                    next_pos(pos_direction, (curr_x, curr_y), grid)
-                        .map(|coords| (coords, pos_direction))
-                )
-                .map(|(pos_coords, pos_direction)| {
-                    if pos_direction == prev_direction {
-                        ((pos_coords, (prev_duration+1, prev_direction.clone())), grid[pos_coords.0][pos_coords.1])
-                    } else {
-                        ((pos_coords, (1, pos_direction.clone())), grid[pos_coords.0][pos_coords.1])
-                    }
-                })
-                .collect_vec()
-        },
-        |(position, (prev_duration, _))| {
-            *position == (x_len-1, y_len-1) && *prev_duration >= 4
+    let mut lower_bound = vec![vec![u32::MAX; heat_loss[0].len()]; heat_loss.len()];
+    for (cell, (_, heat)) in reached {
+        if let Some((x, y)) = cell {
+            lower_bound[x][y] = heat;
         }
-    )
-    .expect("Should have valid path");
+    }
+
+    lower_bound
+}
+
+#[aoc(day17, part1, Dijkstra)]
+fn part_one(grid: &Grid) -> u32 {
+    Crucible::REGULAR
+        .dijkstra(&City::new(grid))
+        .expect("Should have valid path")
+        .1
+}
 
-    shortest_path.1
+#[aoc(day17, part1, Astar)]
+fn part_one_astar(grid: &Grid) -> u32 {
+    Crucible::REGULAR
+        .astar(&City::new(grid))
+        .expect("Should have valid path")
+        .1
+}
+
+#[aoc(day17, part1, AstarLowerBound)]
+fn part_one_astar_lower_bound(grid: &Grid) -> u32 {
+    Crucible::REGULAR
+        .astar_lower_bound(&City::new(grid))
+        .expect("Should have valid path")
+        .1
+}
+
+#[aoc(day17, part2, Dijkstra)]
+fn part_two(grid: &Grid) -> u32 {
+    Crucible::ULTRA
+        .dijkstra(&City::new(grid))
+        .expect("Should have valid path")
+        .1
 }
 
 #[aoc(day17, part2, Astar)]
 fn part_two_astar(grid: &Grid) -> u32 {
-    let (x_len, y_len) = (grid.len(), grid[0].len());
+    Crucible::ULTRA
+        .astar(&City::new(grid))
+        .expect("Should have valid path")
+        .1
+}
 
-    let shortest_path: (Vec<State>, u32) = astar(
-        &((0, 0), (0, Direction::Right)),
-        |((curr_x, curr_y), (prev_duration, prev_direction))| {
-            let complement = prev_direction.complement();
-            Direction::ALL_DIRECTIONS
+#[aoc(day17, part2, AstarLowerBound)]
+fn part_two_astar_lower_bound(grid: &Grid) -> u32 {
+    Crucible::ULTRA
+        .astar_lower_bound(&City::new(grid))
+        .expect("Should have valid path")
+        .1
+}
+
+/// Draws the path over the grid the way the puzzle does, marking each cell
+/// entered with the direction it was entered from.
+pub fn render_path(grid: &Grid, path: &[State]) -> String {
+    let mut canvas = grid
+        .iter()
+        .map(|row|
+            row
                 .iter()
-                .filter(|pos_direction| {
-                    if **pos_direction == complement {
-                        false
-                    } else if *prev_duration < 4 {
-                        *pos_direction == prev_direction
-                    } else if *prev_duration == 10 {
-                        *pos_direction != prev_direction
-                    } else {
-                        true
-                    }
-                })
-                .filter_map(|pos_direction|
-                    next_pos(pos_direction, (curr_x, curr_y), grid)
-                        .map(|coords| (coords, pos_direction))
-                )
-                .map(|(pos_coords, pos_direction)| {
-                    if pos_direction == prev_direction {
-                        ((pos_coords, (prev_duration+1, prev_direction.clone())), grid[pos_coords.0][pos_coords.1])
-                    } else {
-                        ((pos_coords, (1, pos_direction.clone())), grid[pos_coords.0][pos_coords.1])
-                    }
-                })
+                .map(|heat| char::from_digit(*heat, 10).unwrap())
                 .collect_vec()
-        },
-        |((curr_x, curr_y), _)| {
-            let (target_x, target_y) = (x_len-1, y_len-1);
-            ((target_x - curr_x) + (target_y - curr_y)) as u32
-        },
-        |(position, (prev_duration, _))| {
-            *position == (x_len-1, y_len-1) && *prev_duration >= 4
-        }
-    )
-    .expect("Should have valid path");
+        )
+        .collect_vec();
+
+    for ((x, y), (_, direction)) in path.iter().skip(1) {
+        canvas[*x][*y] = direction.arrow();
+    }
 
-    shortest_path.1
+    canvas
+        .into_iter()
+        .map(|row| row.into_iter().collect::<String>() + "\n")
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_random::XorShift;
     use indoc::indoc;
 
+    // Heat lost along `path`, or `None` if some step is not a legal move
+    fn path_heat_loss(crucible: &Crucible, city: &City, path: &[State]) -> Option<u32> {
+        if !city.starts.contains(&path.first()?.0) {
+            return None
+        }
+        path
+            .iter()
+            .tuple_windows()
+            .try_fold(0, |total, (prev, next)|
+                crucible
+                    .successors(city, prev)
+                    .into_iter()
+                    .find(|(state, _)| state == next)
+                    .map(|(_, heat_loss)| total + heat_loss)
+            )
+    }
+
     #[test]
     fn part1_1() {
         let input = indoc! {"
@@ -316,4 +417,163 @@ mod tests {
         let result = part_two(&input_generator(input));
         assert_eq!(result, 71);
     }
+
+    #[test]
+    fn render_1() {
+        let input = indoc! {"
+            2413432311323
+            3215453535623
+            3255245654254
+            3446585845452
+            4546657867536
+            1438598798454
+            4457876987766
+            3637877979653
+            4654967986887
+            4564679986453
+            1224686865563
+            2546548887735
+            4322674655533
+        "};
+        let grid = input_generator(input);
+        let city = City::new(&grid);
+        let (path, heat_loss) = Crucible::REGULAR.dijkstra(&city).unwrap();
+        assert_eq!(heat_loss, 102);
+
+        // Several paths lose 102, so check the one found rather than the
+        // puzzle's drawing
+        assert_eq!(path_heat_loss(&Crucible::REGULAR, &city, &path), Some(102));
+        assert!(Crucible::REGULAR.is_goal(&city, path.last().unwrap()));
+        let arrows = render_path(&grid, &path).chars().filter(|c| "^v<>".contains(*c)).count();
+        assert_eq!(arrows, path.len() - 1);
+    }
+
+    #[test]
+    fn render_2() {
+        let input = indoc! {"
+            111111111111
+            999999999991
+            999999999991
+            999999999991
+            999999999991
+        "};
+        let grid = input_generator(input);
+        let (path, heat_loss) = Crucible::ULTRA.dijkstra(&City::new(&grid)).unwrap();
+        assert_eq!(heat_loss, 71);
+        assert_eq!(render_path(&grid, &path), indoc! {"
+            1>>>>>>>1111
+            9999999v9991
+            9999999v9991
+            9999999v9991
+            9999999v>>>>
+        "});
+    }
+
+    #[test]
+    fn astar_1() {
+        let input = indoc! {"
+            2413432311323
+            3215453535623
+            3255245654254
+            3446585845452
+            4546657867536
+            1438598798454
+            4457876987766
+            3637877979653
+            4654967986887
+            4564679986453
+            1224686865563
+            2546548887735
+            4322674655533
+        "};
+        let grid = input_generator(input);
+        assert_eq!(part_one_astar(&grid), 102);
+        assert_eq!(part_two_astar(&grid), 94);
+        assert_eq!(part_one_astar_lower_bound(&grid), 102);
+        assert_eq!(part_two_astar_lower_bound(&grid), 94);
+    }
+
+    #[test]
+    fn turn_penalties_1() {
+        let input = indoc! {"
+            1111
+            1991
+            1111
+        "};
+        let grid = input_generator(input);
+        let penalties = vec![
+            vec![0, 0, 0, 5],
+            vec![0, 0, 0, 0],
+            vec![0, 0, 0, 0]
+        ];
+        let city = City { turn_penalties: Some(&penalties), ..City::new(&grid) };
+        let (path, heat_loss) = Crucible { min_run: 1, max_run: 3, can_reverse: false }.dijkstra(&city).unwrap();
+        assert_eq!(heat_loss, 5);
+        assert_eq!(render_path(&grid, &path), indoc! {"
+            1111
+            v991
+            v>>>
+        "});
+    }
+
+    #[test]
+    fn multiple_starts_1() {
+        let input = indoc! {"
+            19999
+            19111
+            11191
+        "};
+        let grid = input_generator(input);
+        let city = City {
+            starts: vec![(0, 0), (1, 4)],
+            goals: vec![(2, 0), (2, 2)],
+            ..City::new(&grid)
+        };
+        let crucible = Crucible { min_run: 1, max_run: 10, can_reverse: true };
+        let (path, heat_loss) = crucible.dijkstra(&city).unwrap();
+        assert_eq!(heat_loss, 2);
+        assert_eq!(path.first().unwrap().0, (0, 0));
+        assert_eq!(path.last().unwrap().0, (2, 0));
+        assert_eq!(crucible.astar(&city).unwrap().1, 2);
+    }
+
+    #[test]
+    fn reverse_1() {
+        let input = indoc! {"
+            1999
+        "};
+        let grid = input_generator(input);
+        let city = City { goals: vec![(0, 0)], starts: vec![(0, 1)], ..City::new(&grid) };
+        let crucible = Crucible { min_run: 1, max_run: 3, can_reverse: true };
+        assert_eq!(crucible.dijkstra(&city).unwrap().1, 1);
+    }
+
+    #[test]
+    fn astar_lower_bound_random() {
+        let mut random = XorShift::new(2023);
+
+        let (mut dijkstra_expanded, mut astar_expanded) = (0, 0);
+        for _ in 0..40 {
+            let (x_len, y_len) = (5 + random.below(20) as usize, 5 + random.below(20) as usize);
+            let grid = (0..x_len)
+                .map(|_| (0..y_len).map(|_| 1 + random.below(9) as u32).collect())
+                .collect::<Grid>();
+            let mut city = City::new(&grid);
+            if random.below(2) == 0 {
+                city.starts.push((random.below(x_len as u64) as usize, 0));
+                city.goals.push((0, random.below(y_len as u64) as usize));
+            }
+
+            for crucible in [Crucible::REGULAR, Crucible::ULTRA] {
+                let (expected, expected_count) = crucible.dijkstra_counted(&city);
+                let (result, result_count) = crucible.astar_lower_bound_counted(&city);
+                assert_eq!(result.map(|path| path.1), expected.map(|path| path.1));
+                dijkstra_expanded += expected_count;
+                astar_expanded += result_count;
+            }
+        }
+
+        println!("Dijkstra expanded {dijkstra_expanded} states, A* expanded {astar_expanded}");
+        assert!(astar_expanded < dijkstra_expanded);
+    }
 }
@@ -1,14 +1,14 @@
-use std::collections::HashSet;
-use rayon::prelude::*;
+use crate::polygon::Polygon;
 
-enum Direction {
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
     Up,
     Down,
     Left,
     Right
 }
 
-fn add_direction(direction: &Direction) -> (i32, i32) {
+fn add_direction(direction: &Direction) -> (i64, i64) {
     match direction {
         Direction::Up => (-1, 0),
         Direction::Down => (1, 0),
@@ -17,164 +17,232 @@ fn add_direction(direction: &Direction) -> (i32, i32) {
     }
 }
 
-#[aoc_generator(day18, part1)]
-fn input_generator(input: &str) -> Vec<(Direction, i32)> {
-    input
-        .lines()
-        .map(|line| {
-            let mut parts = line.split_whitespace();
-            let direction = match parts.next().unwrap() {
-                "U" => Direction::Up,
-                "D" => Direction::Down,
-                "L" => Direction::Left,
-                "R" => Direction::Right,
-                _ => panic!("Shouldn't go this direction")
-            };
-            let distance: i32 = parts.next().unwrap().parse().unwrap();
-
-            (direction, distance)
-        })
-        .collect()
+/// One line of the dig plan, such as `R 6 (#70c710)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DigInstruction {
+    pub direction: Direction,
+    pub distance: i64,
+    pub color: u32
 }
 
-#[aoc(day18, part1)]
-fn part_one(input: &[(Direction, i32)]) -> usize {
-    let (mut min_x, mut min_y) = (i32::MAX, i32::MAX);
-    let (mut max_x, mut max_y) = (i32::MIN, i32::MIN);
-    let mut map = HashSet::new();
-
-    let mut curr_location = (0, 0);
-    map.insert(curr_location);
-    for (direction, steps) in input {
-        let adder = add_direction(direction);
-        for _ in 0..*steps {
-            curr_location = (curr_location.0+adder.0, curr_location.1+adder.1);
-            map.insert(curr_location);
+#[derive(Debug, PartialEq)]
+pub enum PlanError {
+    /// Line without a direction, distance and colour.
+    MalformedLine(String),
+    /// Direction that is not one of `U`, `D`, `L` or `R`.
+    InvalidDirection(String),
+    /// Distance that is not a positive number.
+    InvalidDistance(String),
+    /// Colour that is not written as `(#rrggbb)`.
+    InvalidColor(String),
+    /// Colour whose last digit is not a direction from 0 to 3.
+    InvalidHexDirection(u32),
+    /// Plan that stops at this `(row, column)` instead of back where it started.
+    Unclosed((i64, i64)),
+    /// Plan that digs further from where it started than an `i64` can hold.
+    Overflow
+}
 
+impl DigInstruction {
+    pub fn parse(line: &str) -> Result<Self, PlanError> {
+        let [direction, distance, color] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+            return Err(PlanError::MalformedLine(line.to_string()))
+        };
+        let direction = match direction {
+            "U" => Direction::Up,
+            "D" => Direction::Down,
+            "L" => Direction::Left,
+            "R" => Direction::Right,
+            _ => return Err(PlanError::InvalidDirection(direction.to_string()))
+        };
+        let distance = match distance.parse() {
+            Ok(distance) if distance > 0 => distance,
+            _ => return Err(PlanError::InvalidDistance(distance.to_string()))
+        };
+        let color = color
+            .strip_prefix("(#")
+            .and_then(|color| color.strip_suffix(')'))
+            .filter(|hex| hex.len() == 6)
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| PlanError::InvalidColor(color.to_string()))?;
 
-            min_x = std::cmp::min(min_x, curr_location.0);
-            min_y = std::cmp::min(min_y, curr_location.1);
+        Ok(DigInstruction { direction, distance, color })
+    }
 
+    /// The instruction hidden in the colour: the first five hex digits are the
+    /// distance and the last is the direction, 0 to 3 for `R`, `D`, `L`, `U`.
+    pub fn decode_color(&self) -> Result<Self, PlanError> {
+        let direction = match self.color & 0xf {
+            0 => Direction::Right,
+            1 => Direction::Down,
+            2 => Direction::Left,
+            3 => Direction::Up,
+            _ => return Err(PlanError::InvalidHexDirection(self.color))
+        };
 
-            max_x = std::cmp::max(max_x, curr_location.0);
-            max_y = std::cmp::max(max_y, curr_location.1);
-        }
+        Ok(DigInstruction { direction, distance: (self.color >> 4) as i64, color: self.color })
     }
+}
 
-    (min_x..=max_x)
-        .flat_map(|i|
-            (min_y..=max_y)
-                .map(move |j| (i, j))
-        )
-        .par_bridge()
-        .map(|(i, j)| {
-            if map.contains(&(i.to_owned(), j)) {
-                1
-            } else {
-                let mut cursor = (i-1, j);
-                let mut inside_tracker = false;
-
-                while cursor.0 >= min_x {
-                    if map.contains(&cursor) {
-                        let left_neighbor = map.get(&(cursor.0, cursor.1-1));
-                        let right_neighbor = map.get(&(cursor.0, cursor.1+1));
-                        let start_y = match (left_neighbor, right_neighbor) {
-                            (Some(_), Some(_)) => {
-                                inside_tracker = !inside_tracker;
-                                cursor.0 -= 1;
-                                continue
-                            },
-                            (Some((_, y)), None) => y,
-                            (None, Some((_, y))) => y,
-                            (None, None) => panic!("A wall should not be by itself")
-                        };
-
-                        cursor.0 -= 1;
-                        let end_y = loop {
-                            let left_neighbor = map.get(&(cursor.0, cursor.1-1));
-                            let right_neighbor = map.get(&(cursor.0, cursor.1+1));
-                            match (left_neighbor, right_neighbor) {
-                                (Some(_), Some(_)) => panic!("Shouldn't find a horizontal wall"),
-                                (Some((_, y)), None) => break y,
-                                (None, Some((_, y))) => break y,
-                                (None, None) => ()
-                            };
-                            cursor.0 -= 1;
-                        };
-                        if start_y != end_y {
-                            inside_tracker = !inside_tracker
-                        }
-                    }
-                    cursor.0 -= 1;
-                }
-
-                if inside_tracker {
-                    1
-                } else {
-                    0
-                }
-            }
+/// Parses the plan and checks that it ends back where digging started.
+pub fn parse_plan(input: &str) -> Result<Vec<DigInstruction>, PlanError> {
+    let plan = input
+        .lines()
+        .map(DigInstruction::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    validate_plan(&plan)?;
+
+    Ok(plan)
+}
+
+pub fn validate_plan(plan: &[DigInstruction]) -> Result<(), PlanError> {
+    let end = plan
+        .iter()
+        .try_fold((0i64, 0i64), |(r, c), DigInstruction { direction, distance, .. }| {
+            let (dr, dc) = add_direction(direction);
+            Some((r.checked_add(dr.checked_mul(*distance)?)?, c.checked_add(dc.checked_mul(*distance)?)?))
         })
-        .sum()
+        .ok_or(PlanError::Overflow)?;
+
+    match end {
+        (0, 0) => Ok(()),
+        end => Err(PlanError::Unclosed(end))
+    }
+}
+
+#[aoc_generator(day18)]
+fn input_generator(input: &str) -> Vec<DigInstruction> {
+    parse_plan(input).expect("Invalid dig plan")
 }
 
-#[aoc(day18, part1, Optimized)]
-fn part_one_optimized(input: &[(Direction, i32)]) -> i32 {
-    let (result, _, _) = input
+fn lagoon_size(plan: &[DigInstruction]) -> i128 {
+    let steps = plan
         .iter()
-        .fold((0, 0, 0), |(mut a, mut r, mut c), (direction, distance)| {
-            let (rr, cc) = (r,c);
-            match direction {
-                Direction::Up => r -= distance,
-                Direction::Right => c += distance,
-                Direction::Down => r += distance,
-                Direction::Left => c -= distance
-            };
-            a += (c + cc) * (r - rr) + distance;
-            (a, r, c)
-        });
-    result / 2 + 1
+        .map(|DigInstruction { direction, distance, .. }| (add_direction(direction), *distance));
+
+    Polygon::from_steps(steps)
+        .and_then(|lagoon| lagoon.lattice_points())
+        .expect("Lagoon is too large to measure")
 }
 
-#[aoc_generator(day18, part2)]
-fn input_generator_two(input: &str) -> Vec<(Direction, i64)> {
-    input
-        .lines()
-        .map(|line| {
-            let mut parts = line.split_whitespace();
-            let full_hex = parts.nth(2).unwrap();
-
-            let distance = i64::from_str_radix(&full_hex[2..7], 16).unwrap();
-            let direction_num = i64::from_str_radix(&full_hex[7..8], 16).unwrap();
-            let direction = match direction_num {
-                0 => Direction::Right,
-                1 => Direction::Down,
-                2 => Direction::Left,
-                3 => Direction::Up,
-                _ => panic!("Not a valid direction")
-            };
-
-            (direction, distance)
-        })
-        .collect()
+#[aoc(day18, part1)]
+fn part_one(plan: &[DigInstruction]) -> i128 {
+    lagoon_size(plan)
 }
 
 #[aoc(day18, part2)]
-fn part_two(input: &[(Direction, i64)]) -> i64 {
-    let (result, _, _) = input
+fn part_two(plan: &[DigInstruction]) -> i128 {
+    let plan = plan
         .iter()
-        .fold((0, 0, 0), |(mut a, mut r, mut c), (direction, distance)| {
-            let (rr, cc) = (r,c);
-            match direction {
-                Direction::Up => r -= distance,
-                Direction::Right => c += distance,
-                Direction::Down => r += distance,
-                Direction::Left => c -= distance
-            };
-            a += (c + cc) * (r - rr) + distance;
-            (a, r, c)
-        });
-    result / 2 + 1
+        .map(DigInstruction::decode_color)
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Invalid colour instruction");
+    validate_plan(&plan).expect("Colour instructions do not close the lagoon");
+
+    lagoon_size(&plan)
+}
+
+/// One straight run of the trench, as `(row, column)` from where digging
+/// started, painted in the colour from its line of the plan.
+#[derive(Debug, PartialEq)]
+pub struct Edge {
+    pub from: (i64, i64),
+    pub to: (i64, i64),
+    pub color: String
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Trench {
+    pub edges: Vec<Edge>
+}
+
+impl Trench {
+    pub fn new(plan: &[DigInstruction]) -> Self {
+        let mut curr = (0, 0);
+        let edges = plan
+            .iter()
+            .map(|DigInstruction { direction, distance, color }| {
+                let (dr, dc) = add_direction(direction);
+                let from = curr;
+                curr = (curr.0 + dr * distance, curr.1 + dc * distance);
+                Edge { from, to: curr, color: format!("#{color:06x}") }
+            })
+            .collect();
+
+        Trench { edges }
+    }
+
+    /// Top left and bottom right corners of the trench.
+    fn bounds(&self) -> ((i64, i64), (i64, i64)) {
+        let points = || self.edges.iter().map(|edge| edge.from);
+        (
+            (points().map(|(r, _)| r).min().unwrap_or(0), points().map(|(_, c)| c).min().unwrap_or(0)),
+            (points().map(|(r, _)| r).max().unwrap_or(0), points().map(|(_, c)| c).max().unwrap_or(0))
+        )
+    }
+
+    fn on_trench(&self, (r, c): (i64, i64)) -> bool {
+        self.edges
+            .iter()
+            .any(|Edge { from, to, .. }|
+                (from.0.min(to.0)..=from.0.max(to.0)).contains(&r)
+                    && (from.1.min(to.1)..=from.1.max(to.1)).contains(&c)
+            )
+    }
+
+    /// Casts a ray to the right, counting the vertical runs it crosses. Runs
+    /// include their top end but not their bottom, so a ray running along a
+    /// horizontal run counts it only if the trench carries on across the row.
+    fn inside(&self, (r, c): (i64, i64)) -> bool {
+        self.edges
+            .iter()
+            .filter(|Edge { from, to, .. }|
+                from.1 == to.1 && from.1 > c && from.0.min(to.0) <= r && r < from.0.max(to.0)
+            )
+            .count() % 2 == 1
+    }
+
+    /// Draws the plan the way the puzzle does, `#` for dug out and `.` for
+    /// untouched ground. With `filled` the interior is dug out too. Meant
+    /// for small plans, as it draws every cell.
+    pub fn render(&self, filled: bool) -> String {
+        let ((min_r, min_c), (max_r, max_c)) = self.bounds();
+        (min_r..=max_r)
+            .map(|r| {
+                let mut line = (min_c..=max_c)
+                    .map(|c|
+                        if self.on_trench((r, c)) || (filled && self.inside((r, c))) { '#' } else { '.' }
+                    )
+                    .collect::<String>();
+                line.push('\n');
+                line
+            })
+            .collect()
+    }
+
+    /// An SVG of the lagoon with each run of the trench drawn in its colour.
+    pub fn to_svg(&self) -> String {
+        let ((min_r, min_c), (max_r, max_c)) = self.bounds();
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n",
+            min_c - 1, min_r - 1, max_c - min_c + 2, max_r - min_r + 2
+        );
+        let points = self.edges
+            .iter()
+            .map(|Edge { from: (r, c), .. }| format!("{c},{r}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        svg.push_str(&format!("  <polygon points=\"{points}\" fill=\"#dddddd\" />\n"));
+        for Edge { from, to, color } in self.edges.iter() {
+            svg.push_str(&format!(
+                "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{color}\" stroke-width=\"2\" vector-effect=\"non-scaling-stroke\" />\n",
+                from.1, from.0, to.1, to.0
+            ));
+        }
+        svg.push_str("</svg>\n");
+
+        svg
+    }
 }
 
 #[cfg(test)]
@@ -222,7 +290,90 @@ mod tests {
             L 2 (#015232)
             U 2 (#7a21e3)
         "};
-        let result = part_two(&input_generator_two(input));
+        let result = part_two(&input_generator(input));
         assert_eq!(result, 952_408_144_115);
     }
+
+    #[test]
+    fn render_1() {
+        let input = indoc!{"
+            R 6 (#70c710)
+            D 5 (#0dc571)
+            L 2 (#5713f0)
+            D 2 (#d2c081)
+            R 2 (#59c680)
+            D 2 (#411b91)
+            L 5 (#8ceee2)
+            U 2 (#caa173)
+            L 1 (#1b58a2)
+            U 2 (#caa171)
+            R 2 (#7807d2)
+            U 3 (#a77fa3)
+            L 2 (#015232)
+            U 2 (#7a21e3)
+        "};
+        let trench = Trench::new(&input_generator(input));
+        assert_eq!(trench.render(false), indoc!{"
+            #######
+            #.....#
+            ###...#
+            ..#...#
+            ..#...#
+            ###.###
+            #...#..
+            ##..###
+            .#....#
+            .######
+        "});
+        assert_eq!(trench.render(true), indoc!{"
+            #######
+            #######
+            #######
+            ..#####
+            ..#####
+            #######
+            #####..
+            #######
+            .######
+            .######
+        "});
+    }
+
+    #[test]
+    fn svg_1() {
+        let trench = Trench::new(&input_generator("R 2 (#ff0000)\nD 1 (#00ff00)\nL 2 (#0000ff)\nU 1 (#000000)\n"));
+        assert_eq!(trench.to_svg(), indoc!{r##"
+            <svg xmlns="http://www.w3.org/2000/svg" viewBox="-1 -1 4 3">
+              <polygon points="0,0 2,0 2,1 0,1" fill="#dddddd" />
+              <line x1="0" y1="0" x2="2" y2="0" stroke="#ff0000" stroke-width="2" vector-effect="non-scaling-stroke" />
+              <line x1="2" y1="0" x2="2" y2="1" stroke="#00ff00" stroke-width="2" vector-effect="non-scaling-stroke" />
+              <line x1="2" y1="1" x2="0" y2="1" stroke="#0000ff" stroke-width="2" vector-effect="non-scaling-stroke" />
+              <line x1="0" y1="1" x2="0" y2="0" stroke="#000000" stroke-width="2" vector-effect="non-scaling-stroke" />
+            </svg>
+        "##});
+    }
+
+    #[test]
+    fn parse_plan_1() {
+        let instruction = DigInstruction::parse("R 6 (#70c710)").unwrap();
+        assert_eq!(instruction, DigInstruction { direction: Direction::Right, distance: 6, color: 0x70c710 });
+        assert_eq!(
+            instruction.decode_color(),
+            Ok(DigInstruction { direction: Direction::Right, distance: 461_937, color: 0x70c710 })
+        );
+
+        assert_eq!(parse_plan("R 6"), Err(PlanError::MalformedLine("R 6".to_string())));
+        assert_eq!(parse_plan("X 6 (#70c710)"), Err(PlanError::InvalidDirection("X".to_string())));
+        assert_eq!(parse_plan("R -6 (#70c710)"), Err(PlanError::InvalidDistance("-6".to_string())));
+        assert_eq!(parse_plan("R 6 (70c710)"), Err(PlanError::InvalidColor("(70c710)".to_string())));
+        assert_eq!(parse_plan("R 6 (#70c710)\nD 2 (#0dc571)"), Err(PlanError::Unclosed((2, 6))));
+        assert_eq!(
+            parse_plan(&format!("R {} (#70c710)\nR 1 (#70c710)", i64::MAX)),
+            Err(PlanError::Overflow)
+        );
+        assert_eq!(
+            DigInstruction::parse("R 6 (#70c714)").unwrap().decode_color(),
+            Err(PlanError::InvalidHexDirection(0x70c714))
+        );
+    }
 }
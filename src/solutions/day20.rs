@@ -1,42 +1,94 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+use itertools::Itertools;
 use num::integer::lcm;
+use pathfinding::directed::strongly_connected_components::strongly_connected_components;
+use crate::cycle::hashed_within;
 
-type ModuleMap<'a> = HashMap<String, (Module, Vec<String>)>;
+pub type ModuleMap = HashMap<String, (Module, Vec<String>)>;
 
 #[derive(Clone, PartialEq, Debug)]
-enum Pulse {
+pub enum Pulse {
     Low,
     High
 }
 
-#[derive(Debug, PartialEq)]
-enum Module {
+#[derive(Clone, Debug, PartialEq)]
+pub enum Module {
     Broadcaster,
     FlipFlop(bool),
     Conjunction(HashMap<String, Pulse>)
 }
 
-fn input_generator(input: &str) -> ModuleMap {
-    let mut map = input
-        .lines()
-        .map(|line| {
-            let (module, destinations) = line.split_once(" -> ").unwrap();
-            let (name, module) = match module.chars().nth(0).unwrap() {
-                'b' => ("broadcaster", Module::Broadcaster),
-                '%' => (&module[1..], Module::FlipFlop(false)),
-                '&' => (&module[1..], Module::Conjunction(HashMap::new())),
-                _ => panic!("This is not a valid module")
-            };
-            let destinations = destinations.split(", ").map(|dest| dest.to_string()).collect::<Vec<_>>();
-            (name.to_string(), (module, destinations))
-        })
-        .collect::<HashMap<_,_>>();
+impl Module {
+    /// Level of the pulse this module sends given its current state.
+    fn output(&self) -> Pulse {
+        match self {
+            Module::Broadcaster => Pulse::Low,
+            Module::FlipFlop(state) => if *state { Pulse::High } else { Pulse::Low },
+            Module::Conjunction(prev_states) => {
+                if prev_states.values().all(|prev_pulse| *prev_pulse == Pulse::High) {
+                    Pulse::Low
+                } else {
+                    Pulse::High
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Diagnostic {
+    /// Line without a ` -> ` separator.
+    MalformedLine(String),
+    /// Module whose name has neither a `%` nor `&` prefix and is not the broadcaster.
+    UnknownModule(String),
+    /// Module defined more than once; the first definition is kept.
+    DuplicateModule(String),
+    /// Destination that is never defined, such as `output` or `rx`.
+    UndefinedSink(String),
+    /// Conjunction that no module sends to, so it can never fire low.
+    UnconnectedConjunction(String),
+    /// Flip-flop that no pulse from the broadcaster can reach.
+    UnreachableFlipFlop(String)
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        matches!(self, Diagnostic::MalformedLine(_) | Diagnostic::UnknownModule(_) | Diagnostic::DuplicateModule(_))
+    }
+}
+
+fn parse_modules(input: &str) -> (ModuleMap, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+    let mut map = ModuleMap::new();
+    for line in input.lines() {
+        let Some((module, destinations)) = line.split_once(" -> ") else {
+            diagnostics.push(Diagnostic::MalformedLine(line.to_string()));
+            continue
+        };
+        let (name, module) = match (module, module.chars().next()) {
+            ("broadcaster", _) => ("broadcaster", Module::Broadcaster),
+            (_, Some('%')) => (&module[1..], Module::FlipFlop(false)),
+            (_, Some('&')) => (&module[1..], Module::Conjunction(HashMap::new())),
+            _ => {
+                diagnostics.push(Diagnostic::UnknownModule(module.to_string()));
+                continue
+            }
+        };
+        if map.contains_key(name) {
+            diagnostics.push(Diagnostic::DuplicateModule(name.to_string()));
+            continue
+        }
+        let destinations = destinations.split(", ").map(|dest| dest.to_string()).collect::<Vec<_>>();
+        map.insert(name.to_string(), (module, destinations));
+    }
 
     let conjunctions = map
         .iter()
         .filter(|(_, (module, _))| matches!(module, Module::Conjunction(_)))
         .map(|(name, _)| name)
         .cloned()
+        .sorted()
         .collect::<Vec<_>>();
 
     for conjunction in conjunctions {
@@ -46,143 +98,473 @@ fn input_generator(input: &str) -> ModuleMap {
             .map(|(name, _)| (name.clone(), Pulse::Low))
             .collect::<HashMap<_, _>>();
 
+        if inputs.is_empty() {
+            diagnostics.push(Diagnostic::UnconnectedConjunction(conjunction.clone()));
+        }
         let insertion = map.get_mut(&conjunction).unwrap();
         insertion.0 = Module::Conjunction(inputs);
     }
 
+    let sinks = map
+        .values()
+        .flat_map(|(_, destinations)| destinations)
+        .filter(|dest| !map.contains_key(*dest))
+        .unique()
+        .sorted()
+        .map(|dest| Diagnostic::UndefinedSink(dest.clone()));
+    diagnostics.extend(sinks);
+
+    let mut reachable = HashSet::new();
+    let mut to_visit = vec!["broadcaster"];
+    while let Some(curr_module) = to_visit.pop() {
+        let Some((_, destinations)) = map.get(curr_module) else { continue };
+        if reachable.insert(curr_module) {
+            to_visit.extend(destinations.iter().map(String::as_str));
+        }
+    }
+    let unreachable = map
+        .iter()
+        .filter(|(name, (module, _))| matches!(module, Module::FlipFlop(_)) && !reachable.contains(name.as_str()))
+        .map(|(name, _)| name)
+        .sorted()
+        .map(|name| Diagnostic::UnreachableFlipFlop(name.clone()));
+    diagnostics.extend(unreachable.collect_vec());
+
+    (map, diagnostics)
+}
+
+fn input_generator(input: &str) -> ModuleMap {
+    let (map, diagnostics) = parse_modules(input);
+    let errors = diagnostics
+        .into_iter()
+        .filter(Diagnostic::is_error)
+        .collect::<Vec<_>>();
+    assert!(errors.is_empty(), "This is not a valid module configuration: {errors:?}");
+
     map
 }
 
-#[aoc(day20, part1)]
-fn part_one(input: &str) -> usize {
-    let mut module_map = input_generator(input);
+/// A single pulse in flight: `(sender, pulse, receiver)`.
+pub type Transmission = (String, Pulse, String);
 
-    let mut low_pulse_count = 0;
-    let mut high_pulse_count = 0;
-    for _ in 0..1_000 {
-        let mut pulses = VecDeque::new();
-        low_pulse_count += 1;
-        pulses.push_back(("button".to_string(), Pulse::Low, "broadcaster".to_string()));
+#[derive(Clone, Debug)]
+pub struct Circuit {
+    modules: ModuleMap,
+    presses: usize
+}
 
-        while let Some((from_module, curr_pulse, to_module)) = pulses.pop_front() {
-            let Some((curr_module, destinations)) = module_map.get_mut(&to_module) else {
-                continue
-            };
-            match curr_module {
-                Module::Broadcaster => {
-                    for next_module in destinations.iter() {
-                        low_pulse_count += 1;
-                        pulses.push_back((to_module.clone(), Pulse::Low, next_module.clone()))
-                    }
-                },
-                Module::FlipFlop(state) => {
-                    if curr_pulse == Pulse::High {
-                        ()
-                    } else {
-                        *state = !*state;
-                        let (sending_pulse, counter): (_, &mut usize) = state
-                            .then_some((Pulse::High, &mut high_pulse_count))
-                            .unwrap_or((Pulse::Low, &mut low_pulse_count));
-                        for next_module in destinations.iter() {
-                            *counter += 1;
-                            pulses.push_back((to_module.clone(), sending_pulse.clone(), next_module.clone()))
-                        }
-                    }
-                },
-                Module::Conjunction(prev_states) => {
-                    let prev_state = prev_states.get_mut(&from_module).unwrap();
-                    *prev_state = curr_pulse;
+/// Periodic behaviour of one module's output, found by hashing the state of
+/// every module upstream of it after each button press.
+#[derive(Debug, PartialEq)]
+pub struct SignalCycle {
+    /// Presses before the upstream state first repeats.
+    pub offset: usize,
+    /// Presses between repeats of the upstream state.
+    pub period: usize,
+    /// Every press up to `offset + period` on which the pulse was sent.
+    pub hits: Vec<usize>
+}
 
+impl SignalCycle {
+    pub fn sends_on(&self, press: usize) -> bool {
+        let press = if press <= self.offset {
+            press
+        } else {
+            self.offset + (press - self.offset - 1) % self.period + 1
+        };
+        self.hits.contains(&press)
+    }
 
-                    let (sending_pulse, counter) = if prev_states.values().all(|prev_pulse| *prev_pulse == Pulse::High) {
-                        (Pulse::Low, &mut low_pulse_count)
-                    } else {
-                        (Pulse::High, &mut high_pulse_count)
-                    };
-                    for next_module in destinations.iter() {
-                        *counter += 1;
-                        pulses.push_back((to_module.clone(), sending_pulse.clone(), next_module.clone()))
-                    }
+    /// Whether the pulse is sent on exactly the presses that are multiples of
+    /// `period`. The offset is ignored, since memory downstream of a counter,
+    /// such as an inverter's, can take a press to settle into the cycle.
+    fn is_counter(&self) -> bool {
+        self.hits.iter().copied().eq((self.period..=self.offset + self.period).step_by(self.period))
+    }
+}
+
+/// Every pulse sent over a run of button presses, kept per press in queue order.
+#[derive(Debug)]
+pub struct Trace {
+    initial: Vec<(String, Pulse)>,
+    pub presses: Vec<Vec<Transmission>>
+}
+
+impl Trace {
+    /// Renders the trace as a Value Change Dump with one wire per module output.
+    /// A pulse at queue position `i` of press `p` is placed at time
+    /// `p * width + i`, where `width` is the longest press in the trace.
+    pub fn to_vcd(&self) -> String {
+        let width = self.presses.iter().map(Vec::len).max().unwrap_or(0);
+        let ids = self.initial
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| (name.as_str(), vcd_id(i)))
+            .collect::<HashMap<_, _>>();
+        let level = |pulse: &Pulse| if *pulse == Pulse::High { '1' } else { '0' };
+
+        let mut vcd = String::from("$timescale 1ns $end\n$scope module day20 $end\n");
+        for (name, _) in self.initial.iter() {
+            vcd.push_str(&format!("$var wire 1 {} {name} $end\n", ids[name.as_str()]));
+        }
+        vcd.push_str("$upscope $end\n$enddefinitions $end\n#0\n$dumpvars\n");
+        for (name, pulse) in self.initial.iter() {
+            vcd.push_str(&format!("{}{}\n", level(pulse), ids[name.as_str()]));
+        }
+        vcd.push_str("$end\n");
+
+        let mut outputs = self.initial.iter().cloned().collect::<HashMap<_, _>>();
+        for (press, transmissions) in self.presses.iter().enumerate() {
+            for (position, (from, pulse, _)) in transmissions.iter().enumerate() {
+                let Some(output) = outputs.get_mut(from) else { continue };
+                if output != pulse {
+                    *output = pulse.clone();
+                    vcd.push_str(&format!("#{}\n{}{}\n", press * width + position, level(pulse), ids[from.as_str()]));
                 }
             }
         }
+
+        vcd
     }
+}
 
-    low_pulse_count * high_pulse_count
+fn vcd_id(mut index: usize) -> String {
+    let mut id = String::new();
+    loop {
+        id.push((b'!' + (index % 94) as u8) as char);
+        index /= 94;
+        if index == 0 {
+            break id
+        }
+        index -= 1;
+    }
 }
 
-fn find_lcms(mut lcm_modules: Vec<&str>, mut module_map: ModuleMap) -> Vec<usize> {
-    let mut lcms = Vec::new();
+impl Circuit {
+    pub fn new(modules: ModuleMap) -> Self {
+        Circuit { modules, presses: 0 }
+    }
+
+    pub fn from_input(input: &str) -> Self {
+        Circuit::new(input_generator(input))
+    }
+
+    /// Parses the configuration, returning the circuit alongside any warnings,
+    /// or every diagnostic if the configuration has errors.
+    pub fn validate(input: &str) -> Result<(Self, Vec<Diagnostic>), Vec<Diagnostic>> {
+        let (map, diagnostics) = parse_modules(input);
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            Err(diagnostics)
+        } else {
+            Ok((Circuit::new(map), diagnostics))
+        }
+    }
+
+    pub fn presses(&self) -> usize {
+        self.presses
+    }
 
-    let mut button_press_count = 0;
-    'button_pushes: loop {
-        button_press_count += 1;
+    /// Pushes the button once, returning every pulse sent in the order it was
+    /// processed, starting with the button's own low pulse.
+    pub fn press(&mut self) -> Vec<Transmission> {
+        self.presses += 1;
+
+        let mut sent = Vec::new();
         let mut pulses = VecDeque::new();
         pulses.push_back(("button".to_string(), Pulse::Low, "broadcaster".to_string()));
 
         while let Some((from_module, curr_pulse, to_module)) = pulses.pop_front() {
-            lcm_modules.retain(|curr_module| {
-                if from_module == *curr_module && curr_pulse == Pulse::High {
-                    lcms.push(button_press_count);
-                    false
-                } else {
-                    true
-                }
-            });
-            if lcm_modules.len() == 0 { break 'button_pushes }
+            sent.push((from_module.clone(), curr_pulse.clone(), to_module.clone()));
 
-            let Some((curr_module, destinations)) = module_map.get_mut(&to_module) else {
+            let Some((curr_module, destinations)) = self.modules.get_mut(&to_module) else {
                 continue
             };
-            match curr_module {
-                Module::Broadcaster => {
-                    for next_module in destinations.iter() {
-                        pulses.push_back((to_module.clone(), Pulse::Low, next_module.clone()))
-                    }
-                },
+            let sending_pulse = match curr_module {
+                Module::Broadcaster => curr_pulse,
+                Module::FlipFlop(_) if curr_pulse == Pulse::High => continue,
                 Module::FlipFlop(state) => {
-                    if curr_pulse == Pulse::High {
-                        ()
-                    } else {
-                        *state = !*state;
-                        let sending_pulse = state
-                            .then_some(Pulse::High)
-                            .unwrap_or(Pulse::Low);
-                        for next_module in destinations.iter() {
-                            pulses.push_back((to_module.clone(), sending_pulse.clone(), next_module.clone()))
-                        }
-                    }
+                    *state = !*state;
+                    curr_module.output()
                 },
                 Module::Conjunction(prev_states) => {
                     let prev_state = prev_states.get_mut(&from_module).unwrap();
                     *prev_state = curr_pulse;
+                    curr_module.output()
+                }
+            };
+            for next_module in destinations.iter() {
+                pulses.push_back((to_module.clone(), sending_pulse.clone(), next_module.clone()))
+            }
+        }
 
-                    let sending_pulse = if prev_states.values().all(|prev_pulse| *prev_pulse == Pulse::High) {
-                        Pulse::Low
-                    } else {
-                        Pulse::High
-                    };
-                    for next_module in destinations.iter() {
-                        pulses.push_back((to_module.clone(), sending_pulse.clone(), next_module.clone()))
-                    }
+        sent
+    }
+
+    /// Pushes the button `presses` times, recording every pulse for export.
+    pub fn trace(&mut self, presses: usize) -> Trace {
+        let initial = self.modules
+            .iter()
+            .map(|(name, (module, _))| (name.clone(), module.output()))
+            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+            .collect();
+
+        Trace { initial, presses: (0..presses).map(|_| self.press()).collect() }
+    }
+
+    /// Pushes the button `presses` times, returning the low and high pulse counts.
+    pub fn run(&mut self, presses: usize) -> (usize, usize) {
+        (0..presses)
+            .flat_map(|_| self.press())
+            .fold((0, 0), |(low, high), (_, pulse, _)| match pulse {
+                Pulse::Low => (low + 1, high),
+                Pulse::High => (low, high + 1)
+            })
+    }
+
+    fn senders(&self, module: &str) -> Vec<String> {
+        self.modules
+            .iter()
+            .filter(|(_, (_, destinations))| destinations.iter().any(|dest| dest == module))
+            .map(|(name, _)| name.clone())
+            .sorted()
+            .collect()
+    }
+
+    /// Every module whose state can influence `module`, including itself.
+    fn upstream(&self, module: &str) -> Vec<String> {
+        let mut found = HashSet::from([module.to_string()]);
+        let mut to_visit = vec![module.to_string()];
+        while let Some(curr_module) = to_visit.pop() {
+            for sender in self.senders(&curr_module) {
+                if found.insert(sender.clone()) {
+                    to_visit.push(sender)
+                }
+            }
+        }
+
+        found
+            .into_iter()
+            .filter(|name| self.modules.contains_key(name))
+            .sorted()
+            .collect()
+    }
+
+    fn state_key(&self, modules: &[String]) -> Vec<bool> {
+        modules
+            .iter()
+            .flat_map(|name| match &self.modules[name].0 {
+                Module::Broadcaster => Vec::new(),
+                Module::FlipFlop(state) => vec![*state],
+                Module::Conjunction(prev_states) => prev_states
+                    .iter()
+                    .sorted_by(|(a, _), (b, _)| a.cmp(b))
+                    .map(|(_, pulse)| *pulse == Pulse::High)
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Finds the cycle of `module` sending `pulse`, giving up after `max_presses`.
+    pub fn signal_cycle(&self, module: &str, pulse: Pulse, max_presses: usize) -> Option<SignalCycle> {
+        let upstream = self.upstream(module);
+        let mut circuit = self.clone();
+
+        // The circuit is pressed alongside the states, which are stepped in order.
+        let mut hits = Vec::new();
+        let press = |_: &Vec<bool>| {
+            if circuit.press().iter().any(|(from, sent, _)| from == module && *sent == pulse) {
+                hits.push(circuit.presses - self.presses)
+            }
+            circuit.state_key(&upstream)
+        };
+        let (cycle, _) = hashed_within(&self.state_key(&upstream), press, max_presses)?;
+
+        Some(SignalCycle { offset: cycle.start, period: cycle.length, hits })
+    }
+
+    /// The LCM shortcut: `module` is fed by a single conjunction whose inputs are
+    /// independent counters, each sending one high pulse at the end of its cycle.
+    /// The press at the LCM is too far off to simulate, so this assumes the high
+    /// pulses all reach the conjunction before any input resets within that press,
+    /// as they do when each counter's hub resets it after pulsing its output.
+    fn counter_lcm(&self, module: &str, pulse: &Pulse, max_presses: usize) -> Option<usize> {
+        let [feeder] = &self.senders(module)[..] else { return None };
+        if *pulse != Pulse::Low || !matches!(self.modules[feeder].0, Module::Conjunction(_)) {
+            return None
+        }
+
+        let mut claimed = HashSet::new();
+        let mut periods = Vec::new();
+        for input in self.senders(feeder) {
+            let cycle = self.signal_cycle(&input, Pulse::High, max_presses)?;
+            if !cycle.is_counter() {
+                return None
+            }
+            for name in self.upstream(&input) {
+                if self.modules[&name].0 != Module::Broadcaster && !claimed.insert(name) {
+                    return None
                 }
             }
+            periods.push(cycle.period)
         }
+
+        Some(periods.into_iter().fold(1, lcm))
     }
 
-    lcms
+    /// First press (counted from this circuit's current state) on which `module`
+    /// receives `pulse`. Uses the LCM shortcut when the circuit has that shape and
+    /// otherwise falls back to simulating up to `max_presses` presses.
+    pub fn first_press_receiving(&self, module: &str, pulse: Pulse, max_presses: usize) -> Option<usize> {
+        if let Some(presses) = self.counter_lcm(module, &pulse, max_presses) {
+            return Some(presses)
+        }
+
+        let mut circuit = self.clone();
+        (1..=max_presses).find(|_|
+            circuit
+                .press()
+                .iter()
+                .any(|(_, received, to)| to == module && *received == pulse)
+        )
+    }
 }
 
-#[aoc(day20, part2)]
-fn part_two(input: &str) -> usize {
-    let module_map: ModuleMap = input_generator(input);
+/// A chain of flip-flops driven by the broadcaster that counts button presses.
+/// Bits wired into `hub` are set in `value`; once they are all on, the hub
+/// resets the counter and pulses `outputs`, so the counter fires every `value`
+/// presses.
+#[derive(Debug, PartialEq)]
+pub struct Counter {
+    pub hub: String,
+    pub bits: Vec<String>,
+    pub value: usize,
+    pub outputs: Vec<String>
+}
 
-    let lcm_modules = vec!["pm", "mk", "pk", "hf"];
-    let lcms = find_lcms(lcm_modules, module_map);
+impl Counter {
+    pub fn width(&self) -> usize {
+        self.bits.len()
+    }
+}
 
-    lcms
+impl Circuit {
+    pub fn strongly_connected_components(&self) -> Vec<Vec<String>> {
+        let nodes = self.modules.keys().sorted().cloned().collect_vec();
+        strongly_connected_components(&nodes, |name| {
+            self.modules[name].1
+                .iter()
+                .filter(|dest| self.modules.contains_key(*dest))
+                .cloned()
+                .collect_vec()
+        })
         .into_iter()
-        .fold(1, lcm)
+        .map(|component| component.into_iter().sorted().collect_vec())
+        .sorted()
+        .collect()
+    }
+
+    fn is_flip_flop(&self, name: &str) -> bool {
+        matches!(self.modules.get(name), Some((Module::FlipFlop(_), _)))
+    }
+
+    fn is_conjunction(&self, name: &str) -> bool {
+        matches!(self.modules.get(name), Some((Module::Conjunction(_), _)))
+    }
+
+    fn counter_from(&self, first_bit: &str) -> Option<Counter> {
+        let mut bits = Vec::new();
+        let mut hub = None;
+        let mut value = 0;
+
+        let mut curr_bit = Some(first_bit.to_string());
+        while let Some(bit) = curr_bit {
+            let destinations = &self.modules[&bit].1;
+            match destinations.iter().filter(|dest| self.is_conjunction(dest)).collect_vec()[..] {
+                [] => (),
+                [bit_hub] => {
+                    if *hub.get_or_insert(bit_hub) != bit_hub {
+                        return None
+                    }
+                    value |= 1 << bits.len()
+                },
+                _ => return None
+            }
+            curr_bit = match destinations.iter().filter(|dest| self.is_flip_flop(dest)).collect_vec()[..] {
+                [] => None,
+                [next_bit] => Some(next_bit.clone()),
+                _ => return None
+            };
+            if bits.contains(&bit) {
+                return None
+            }
+            bits.push(bit);
+        }
+
+        let hub = hub?.clone();
+        let outputs = self.modules[&hub].1
+            .iter()
+            .filter(|dest| !bits.contains(dest))
+            .cloned()
+            .collect();
+
+        Some(Counter { hub, bits, value, outputs })
+    }
+
+    /// Binary counters hanging off the broadcaster, in broadcaster order.
+    pub fn counters(&self) -> Vec<Counter> {
+        let Some((_, starts)) = self.modules.get("broadcaster") else {
+            return Vec::new()
+        };
+
+        starts
+            .iter()
+            .filter(|start| self.is_flip_flop(start))
+            .filter_map(|start| self.counter_from(start))
+            .collect()
+    }
+
+    /// Graphviz rendering of the module graph. Flip-flops are boxes,
+    /// conjunctions diamonds, the broadcaster a double circle and undefined
+    /// destinations plain text.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph day20 {\n");
+        for (name, (module, _)) in self.modules.iter().sorted_by(|(a, _), (b, _)| a.cmp(b)) {
+            let (shape, prefix) = match module {
+                Module::Broadcaster => ("doublecircle", ""),
+                Module::FlipFlop(_) => ("box", "%"),
+                Module::Conjunction(_) => ("diamond", "&")
+            };
+            dot.push_str(&format!("    {name} [shape={shape}, label=\"{prefix}{name}\"];\n"));
+        }
+        for sink in self.modules.values().flat_map(|(_, destinations)| destinations).unique().sorted() {
+            if !self.modules.contains_key(sink) {
+                dot.push_str(&format!("    {sink} [shape=plaintext];\n"));
+            }
+        }
+        for (name, (_, destinations)) in self.modules.iter().sorted_by(|(a, _), (b, _)| a.cmp(b)) {
+            for dest in destinations {
+                dot.push_str(&format!("    {name} -> {dest};\n"));
+            }
+        }
+        dot.push('}');
+        dot.push('\n');
+
+        dot
+    }
+}
+
+#[aoc(day20, part1)]
+fn part_one(input: &str) -> usize {
+    let (low_pulse_count, high_pulse_count) = Circuit::from_input(input).run(1_000);
+
+    low_pulse_count * high_pulse_count
+}
+
+#[aoc(day20, part2)]
+fn part_two(input: &str) -> usize {
+    Circuit::from_input(input)
+        .first_press_receiving("rx", Pulse::Low, 100_000)
+        .expect("rx should eventually receive a low pulse")
 }
 
 #[cfg(test)]
@@ -215,4 +597,238 @@ mod tests {
         let result = part_one(input);
         assert_eq!(result, 11_687_500);
     }
+
+    const COUNTERS: &str = indoc! {"
+        broadcaster -> a, b1
+        %a -> inv1
+        %b1 -> b2
+        %b2 -> inv2
+        &inv1 -> c
+        &inv2 -> c
+        &c -> rx
+    "};
+
+    #[test]
+    fn signal_cycle_1() {
+        let circuit = Circuit::from_input(COUNTERS);
+        let result = circuit.signal_cycle("inv2", Pulse::High, 100);
+        assert_eq!(result, Some(SignalCycle { offset: 0, period: 4, hits: vec![4] }));
+        assert!(result.unwrap().sends_on(12));
+    }
+
+    #[test]
+    fn first_press_1() {
+        let circuit = Circuit::from_input(COUNTERS);
+        assert_eq!(circuit.counter_lcm("rx", &Pulse::Low, 100), Some(4));
+        assert_eq!(circuit.first_press_receiving("rx", Pulse::Low, 100), Some(4));
+    }
+
+    #[test]
+    fn first_press_2() {
+        let input = indoc! {"
+            broadcaster -> a
+            %a -> inv, con
+            &inv -> b
+            %b -> con
+            &con -> output
+        "};
+        let circuit = Circuit::from_input(input);
+        assert_eq!(circuit.counter_lcm("output", &Pulse::Low, 100), None);
+        assert_eq!(circuit.first_press_receiving("output", Pulse::Low, 100), Some(1));
+    }
+
+    #[test]
+    fn vcd_1() {
+        let input = indoc! {"
+            broadcaster -> a, b, c
+            %a -> b
+            %b -> c
+            %c -> inv
+            &inv -> a
+        "};
+        let trace = Circuit::from_input(input).trace(2);
+        assert_eq!(trace.presses[0].len(), 12);
+
+        let result = trace.to_vcd();
+        let expected = indoc! {"
+            $timescale 1ns $end
+            $scope module day20 $end
+            $var wire 1 ! a $end
+            $var wire 1 \" b $end
+            $var wire 1 # broadcaster $end
+            $var wire 1 $ c $end
+            $var wire 1 % inv $end
+            $upscope $end
+            $enddefinitions $end
+            #0
+            $dumpvars
+            0!
+            0\"
+            0#
+            0$
+            1%
+            $end
+            #4
+            1!
+            #5
+            1\"
+            #6
+            1$
+            #7
+            0%
+            #8
+            0!
+            #9
+            0\"
+            #10
+            0$
+            #11
+            1%
+            #16
+            1!
+        "};
+        assert!(result.starts_with(expected));
+    }
+
+    const BINARY_COUNTER: &str = indoc! {"
+        broadcaster -> f0
+        %f0 -> f1, hub
+        %f1 -> f2
+        %f2 -> hub
+        &hub -> f0, f1, inv
+        &inv -> c
+        &c -> rx
+    "};
+
+    #[test]
+    fn first_press_3() {
+        let input = indoc! {"
+            broadcaster -> f0, g0
+            %f0 -> f1, hub1
+            %f1 -> f2
+            %f2 -> hub1
+            &hub1 -> f0, f1, inv1
+            &inv1 -> c
+            %g0 -> g1, hub2
+            %g1 -> hub2
+            &hub2 -> g0, inv2
+            &inv2 -> c
+            &c -> rx
+        "};
+        let circuit = Circuit::from_input(input);
+        let cycle = circuit.signal_cycle("inv1", Pulse::High, 100).unwrap();
+        assert_eq!(cycle, SignalCycle { offset: 1, period: 5, hits: vec![5] });
+        assert_eq!(circuit.counter_lcm("rx", &Pulse::Low, 10), Some(15));
+        assert_eq!(circuit.first_press_receiving("rx", Pulse::Low, 10), Some(15));
+
+        let mut simulated = circuit.clone();
+        let first = (1..=100).find(|_|
+            simulated.press().iter().any(|(_, pulse, to)| to == "rx" && *pulse == Pulse::Low)
+        );
+        assert_eq!(first, Some(15));
+    }
+
+    #[test]
+    fn counters_1() {
+        let circuit = Circuit::from_input(BINARY_COUNTER);
+        let result = circuit.counters();
+        assert_eq!(result, vec![Counter {
+            hub: "hub".to_string(),
+            bits: vec!["f0".to_string(), "f1".to_string(), "f2".to_string()],
+            value: 5,
+            outputs: vec!["inv".to_string()]
+        }]);
+        assert_eq!(result[0].width(), 3);
+
+        let cycle = circuit.signal_cycle("inv", Pulse::High, 100).unwrap();
+        assert_eq!(cycle.period, result[0].value);
+    }
+
+    #[test]
+    fn strongly_connected_components_1() {
+        let circuit = Circuit::from_input(BINARY_COUNTER);
+        let result = circuit.strongly_connected_components();
+        assert_eq!(result, vec![
+            vec!["broadcaster".to_string()],
+            vec!["c".to_string()],
+            vec!["f0".to_string(), "f1".to_string(), "f2".to_string(), "hub".to_string()],
+            vec!["inv".to_string()]
+        ]);
+    }
+
+    #[test]
+    fn dot_1() {
+        let input = indoc! {"
+            broadcaster -> a
+            %a -> inv, con
+            &inv -> b
+            %b -> con
+            &con -> output
+        "};
+        let result = Circuit::from_input(input).to_dot();
+        assert_eq!(result, indoc! {"
+            digraph day20 {
+                a [shape=box, label=\"%a\"];
+                b [shape=box, label=\"%b\"];
+                broadcaster [shape=doublecircle, label=\"broadcaster\"];
+                con [shape=diamond, label=\"&con\"];
+                inv [shape=diamond, label=\"&inv\"];
+                output [shape=plaintext];
+                a -> inv;
+                a -> con;
+                b -> con;
+                broadcaster -> a;
+                con -> output;
+                inv -> b;
+            }
+        "});
+    }
+
+    #[test]
+    fn validate_1() {
+        let input = indoc! {"
+            broadcaster -> a
+            %a -> inv, con
+            &inv -> b
+            %b -> con
+            &con -> output
+        "};
+        let (_, result) = Circuit::validate(input).unwrap();
+        assert_eq!(result, vec![Diagnostic::UndefinedSink("output".to_string())]);
+    }
+
+    #[test]
+    fn validate_2() {
+        let input = indoc! {"
+            broadcaster -> a
+            %a -> b
+            %b -> a
+            %c -> a
+            &lonely -> rx
+        "};
+        let (_, result) = Circuit::validate(input).unwrap();
+        assert_eq!(result, vec![
+            Diagnostic::UnconnectedConjunction("lonely".to_string()),
+            Diagnostic::UndefinedSink("rx".to_string()),
+            Diagnostic::UnreachableFlipFlop("c".to_string())
+        ]);
+    }
+
+    #[test]
+    fn validate_3() {
+        let input = indoc! {"
+            broadcaster -> a
+            %a -> b
+            %a -> c
+            $b -> a
+            nonsense
+        "};
+        let result = Circuit::validate(input).unwrap_err();
+        assert_eq!(result, vec![
+            Diagnostic::DuplicateModule("a".to_string()),
+            Diagnostic::UnknownModule("$b".to_string()),
+            Diagnostic::MalformedLine("nonsense".to_string()),
+            Diagnostic::UndefinedSink("b".to_string())
+        ]);
+    }
 }
@@ -1,7 +1,9 @@
+use std::cmp::Ordering;
+use std::env;
 use itertools::Itertools;
 
-#[derive(Ord, PartialEq, PartialOrd, Eq, Debug)]
-enum HandType {
+#[derive(Ord, PartialEq, PartialOrd, Eq, Debug, Clone, Copy, Hash)]
+pub enum HandType {
     FiveOfKind = 7,
     FourOfKind = 6,
     FullHouse = 5,
@@ -11,91 +13,272 @@ enum HandType {
     HighCard = 1
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
-struct Hand {
-    ty: HandType,
-    cards: [u8; 5],
-    bid: u32,
+impl HandType {
+    /// From weakest to strongest.
+    pub const ALL: [HandType; 7] = [
+        HandType::HighCard,
+        HandType::OnePair,
+        HandType::TwoPair,
+        HandType::ThreeOfKind,
+        HandType::FullHouse,
+        HandType::FourOfKind,
+        HandType::FiveOfKind
+    ];
+
+    /// Whether a hand with these counts of matching cards, largest first,
+    /// holds this type. A hand holds every type it contains, so a full house
+    /// is also a three of a kind, a two pair and a one pair.
+    fn held_by(self, counts: &[u8]) -> bool {
+        let (first, second) = (counts.first().copied().unwrap_or(0), counts.get(1).copied().unwrap_or(0));
+        match self {
+            HandType::FiveOfKind => first >= 5,
+            HandType::FourOfKind => first >= 4,
+            HandType::FullHouse => first >= 3 && second >= 2,
+            HandType::ThreeOfKind => first >= 3,
+            HandType::TwoPair => first >= 2 && second >= 2,
+            HandType::OnePair => first >= 2,
+            HandType::HighCard => true
+        }
+    }
 }
 
-#[aoc_generator(day7, part1)]
-fn input_generator_part_one(input: &str) -> Vec<Hand> {
-    let mut hands = Vec::new();
-    for line in input.lines() {
-        let (cards, bid) = line.split_once(' ').unwrap();
-        let mut card_counts: [u8; 13] = [0; 13];
-        let mut numeric_representation_of_cards = [0; 5];
-        for (i, card) in cards.chars().enumerate() {
-            match card {
-                card if card.is_numeric() => {
-                    let card_number: u8 = card.to_digit(10).unwrap() as u8;
-                    numeric_representation_of_cards[i] = card_number - 2;
-                    card_counts[(card_number - 2) as usize] += 1;
-                },
-                'A' => {
-                    numeric_representation_of_cards[i] = 12;
-                    card_counts[12] += 1;
-                },
-                'K' => {
-                    numeric_representation_of_cards[i] = 11;
-                    card_counts[11] += 1;
-                },
-                'Q' => {
-                    numeric_representation_of_cards[i] = 10;
-                    card_counts[10] += 1;
-                },
-                'J' => {
-                    numeric_representation_of_cards[i] = 9;
-                    card_counts[9] += 1;
-                },
-                'T' => {
-                    numeric_representation_of_cards[i] = 8;
-                    card_counts[8] += 1;
-                },
-                _ => unreachable!()
-            }
+/// How hands with the same type are ordered.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TieBreak {
+    /// Compare the cards in the order they were dealt, as the puzzle does.
+    FirstCard,
+    /// Compare the strongest cards first, as poker does.
+    HighestCard
+}
+
+/// A variant of Camel Cards.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rules {
+    /// Every card, from weakest to strongest.
+    pub card_order: Vec<char>,
+    /// Cards that act as whichever card makes the strongest hand. They still
+    /// break ties by their place in `card_order`.
+    pub wild: Vec<char>,
+    /// Hand types that count, from weakest to strongest. A hand scores as the
+    /// highest ranked type it holds. One holding none of them keeps its own
+    /// type but scores below every ranked hand.
+    pub type_ranking: Vec<HandType>,
+    pub tie_break: TieBreak
+}
+
+impl Rules {
+    /// The rules from part one.
+    pub fn standard() -> Self {
+        Rules {
+            card_order: "23456789TJQKA".chars().collect(),
+            wild: Vec::new(),
+            type_ranking: HandType::ALL.to_vec(),
+            tie_break: TieBreak::FirstCard
         }
-        let card_counts = card_counts
+    }
+
+    /// The rules from part two: `J` is a joker, wild but the weakest card.
+    pub fn jokers() -> Self {
+        Rules {
+            card_order: "J23456789TQKA".chars().collect(),
+            wild: vec!['J'],
+            ..Rules::standard()
+        }
+    }
+
+    fn card_strength(&self, card: char) -> u8 {
+        self.card_order
+            .iter()
+            .position(|&ordered| ordered == card)
+            .unwrap_or_else(|| panic!("{card} is not a card in these rules")) as u8
+    }
+
+    fn ranking(&self, ty: HandType) -> Option<usize> {
+        self.type_ranking.iter().position(|ranked| *ranked == ty)
+    }
+
+    /// The highest ranked type held by cards with no wild cards left in them.
+    fn type_of_played(&self, played: &[char; 5]) -> HandType {
+        let counts = played
+            .iter()
+            .counts()
+            .into_values()
+            .map(|count| count as u8)
+            .sorted_unstable_by(|a, b| b.cmp(a))
+            .collect_vec();
+
+        self.type_ranking
+            .iter()
+            .rev()
+            .find(|ty| ty.held_by(&counts))
+            .or_else(|| HandType::ALL.iter().rev().find(|ty| ty.held_by(&counts)))
+            .copied()
+            .unwrap_or(HandType::HighCard)
+    }
+
+    /// How the wild cards in a hand are played to make its highest ranked
+    /// type, or `None` if it has no wild cards. Each wild card can stand in for
+    /// any other card, so every way of matching the other cards in the hand or
+    /// adding new ones is tried, preferring the most common and strongest
+    /// cards when several make equally good hands.
+    pub fn substitution(&self, cards: &[char; 5]) -> Option<Substitution> {
+        let wild_count = cards.iter().filter(|card| self.wild.contains(card)).count();
+        if wild_count == 0 {
+            return None
+        }
+
+        let in_hand = cards
+            .iter()
+            .filter(|card| !self.wild.contains(card))
+            .counts()
             .into_iter()
-            .filter(|count| *count > 0)
+            .sorted_by_key(|(card, count)| std::cmp::Reverse((*count, self.card_strength(**card))))
+            .map(|(card, _)| *card)
             .collect_vec();
-        let hand_type = match card_counts {
-            count if count.len() == 1 => {
-                HandType::FiveOfKind
-            },
-            count if count.len() == 2 => {
-                if count[0] == 1 || count[0] == 4 {
-                    HandType::FourOfKind
-                } else {
-                    HandType::FullHouse
-                }
-            },
-            count if count.len() == 3 => {
-                if count[0] == 3 || count[1] == 3 || count[2] == 3 {
-                    HandType::ThreeOfKind
-                } else {
-                    HandType::TwoPair
-                }
-            },
-            count if count.len() == 4 => {
-                HandType::OnePair
-            }
-            _ => HandType::HighCard
+        let mut candidates = in_hand.clone();
+        candidates.extend(
+            self.card_order
+                .iter()
+                .rev()
+                .filter(|card| !self.wild.contains(card) && !in_hand.contains(card))
+                .take(wild_count)
+        );
+        if candidates.is_empty() {
+            candidates.extend(self.card_order.last());
+        }
+
+        candidates
+            .into_iter()
+            .combinations_with_replacement(wild_count)
+            .map(|wild_as| {
+                let mut substitutes = wild_as.iter();
+                let played = cards.map(|card|
+                    if self.wild.contains(&card) { *substitutes.next().unwrap() } else { card }
+                );
+                Substitution { ty: self.type_of_played(&played), wild_as, played }
+            })
+            .fold(None, |best: Option<Substitution>, substitution| match best {
+                Some(best) if self.ranking(best.ty) >= self.ranking(substitution.ty) => Some(best),
+                _ => Some(substitution)
+            })
+    }
+
+    /// The highest ranked hand type the cards can make.
+    pub fn classify(&self, cards: &[char; 5]) -> HandType {
+        match self.substitution(cards) {
+            Some(Substitution { ty, .. }) => ty,
+            None => self.type_of_played(cards)
+        }
+    }
+
+    /// The cards in the order ties between them are broken.
+    fn tie_break_cards(&self, cards: &[char; 5]) -> [char; 5] {
+        let mut cards = *cards;
+        if self.tie_break == TieBreak::HighestCard {
+            cards.sort_unstable_by_key(|card| std::cmp::Reverse(self.card_strength(*card)));
+        }
+
+        cards
+    }
+
+    /// Compares two hands dealt under these rules, along with what decided it.
+    pub fn explain(&self, left: &Hand, right: &Hand) -> (Ordering, Decider) {
+        let ordering = (left.strength, left.tie_break).cmp(&(right.strength, right.tie_break));
+        let decider = if left.strength != right.strength {
+            Decider::HandType(left.ty, right.ty)
+        } else {
+            let (left_cards, right_cards) = (self.tie_break_cards(&left.cards), self.tie_break_cards(&right.cards));
+            (0..5)
+                .find(|&position| left.tie_break[position] != right.tie_break[position])
+                .map_or(Decider::Tie, |position| Decider::Card {
+                    position,
+                    left: left_cards[position],
+                    right: right_cards[position]
+                })
         };
-        hands.push(
-            Hand {
-                ty: hand_type,
-                cards: numeric_representation_of_cards,
-                bid: bid.parse().unwrap()
-            },
-        )
+
+        (ordering, decider)
     }
-    hands
 }
 
-#[aoc(day7, part1)]
-fn part_one(input: &[Hand]) -> u32 {
+/// The hand a set of cards plays as once its wild cards stand in for another.
+#[derive(Debug, PartialEq)]
+pub struct Substitution {
+    /// What each wild card stands in for, in the order they were dealt.
+    pub wild_as: Vec<char>,
+    pub played: [char; 5],
+    pub ty: HandType
+}
+
+/// What settled a comparison between two hands.
+#[derive(Debug, PartialEq)]
+pub enum Decider {
+    /// The hands have different types.
+    HandType(HandType, HandType),
+    /// The hands have the same type and first differ at this position, in
+    /// dealt order or strongest first under `TieBreak::HighestCard`.
+    Card { position: usize, left: char, right: char },
+    /// The hands are equally strong.
+    Tie
+}
+
+/// A dealt hand, ordered by how it scores under the rules it was dealt with.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Hand {
+    strength: usize,
+    tie_break: [u8; 5],
+    pub ty: HandType,
+    pub cards: [char; 5],
+    pub bid: u32,
+}
+
+impl Hand {
+    pub fn new(cards: [char; 5], bid: u32, rules: &Rules) -> Self {
+        let ty = rules.classify(&cards);
+        let strength = rules.ranking(ty).map_or(0, |ranking| ranking + 1);
+        let tie_break = rules.tie_break_cards(&cards).map(|card| rules.card_strength(card));
+
+        Hand { strength, tie_break, ty, cards, bid }
+    }
+}
+
+pub fn parse_hands(input: &str, rules: &Rules) -> Vec<Hand> {
     input
+        .lines()
+        .map(|line| {
+            let (cards, bid) = line.split_once(' ').unwrap();
+            let cards = cards
+                .chars()
+                .collect_vec()
+                .try_into()
+                .expect("A hand should have five cards");
+
+            Hand::new(cards, bid.parse().unwrap(), rules)
+        })
+        .collect()
+}
+
+/// Every hand from weakest to strongest with its rank and what it wins.
+pub fn ranking_table(hands: &[Hand]) -> String {
+    let mut table = String::from("Rank  Hand   Type          Bid  Winnings\n");
+    for (i, Hand { ty, cards, bid, .. }) in hands.iter().sorted_unstable().enumerate() {
+        let rank = i + 1;
+        table.push_str(&format!(
+            "{rank:>4}  {}  {:<11} {bid:>5} {:>9}\n",
+            cards.iter().collect::<String>(), format!("{ty:?}"), rank as u32 * bid
+        ));
+    }
+
+    table
+}
+
+/// Set `DAY7_TABLE` when running the solution to print the ranked hands.
+fn total_winnings(hands: &[Hand]) -> u32 {
+    if env::var_os("DAY7_TABLE").is_some() {
+        eprint!("{}", ranking_table(hands));
+    }
+
+    hands
         .iter()
         .sorted_unstable()
         .enumerate()
@@ -104,101 +287,24 @@ fn part_one(input: &[Hand]) -> u32 {
         })
 }
 
+#[aoc_generator(day7, part1)]
+fn input_generator_part_one(input: &str) -> Vec<Hand> {
+    parse_hands(input, &Rules::standard())
+}
+
+#[aoc(day7, part1)]
+fn part_one(input: &[Hand]) -> u32 {
+    total_winnings(input)
+}
+
 #[aoc_generator(day7, part2)]
 fn input_generator_part_two(input: &str) -> Vec<Hand> {
-    let mut hands = Vec::new();
-    for line in input.lines() {
-        let (cards, bid) = line.split_once(' ').unwrap();
-        let mut card_counts: [u8; 13] = [0; 13];
-        let mut numeric_representation_of_cards = [0; 5];
-        for (i, card) in cards.chars().enumerate() {
-            match card {
-                card if card.is_numeric() => {
-                    let card_number: u8 = card.to_digit(10).unwrap() as u8;
-                    numeric_representation_of_cards[i] = card_number - 1;
-                    card_counts[(card_number - 1) as usize] += 1;
-                },
-                'A' => {
-                    numeric_representation_of_cards[i] = 12;
-                    card_counts[12] += 1;
-                },
-                'K' => {
-                    numeric_representation_of_cards[i] = 11;
-                    card_counts[11] += 1;
-                },
-                'Q' => {
-                    numeric_representation_of_cards[i] = 10;
-                    card_counts[10] += 1;
-                },
-                'J' => {
-                    numeric_representation_of_cards[i] = 0;
-                    card_counts[0] += 1;
-                },
-                'T' => {
-                    numeric_representation_of_cards[i] = 9;
-                    card_counts[9] += 1;
-                },
-                _ => unreachable!()
-            }
-        }
-        if card_counts[0] > 0 {
-            let index_of_max = card_counts
-                .iter()
-                .enumerate()
-                .skip(1)
-                .max_by(|(_, a), (_, b)| a.cmp(b))
-                .map(|(index, _)| index)
-                .unwrap();
-            card_counts[index_of_max] += card_counts[0];
-            card_counts[0] = 0;
-        }
-        let card_counts = card_counts
-            .into_iter()
-            .filter(|count| *count > 0)
-            .collect_vec();
-        let hand_type = match card_counts {
-            count if count.len() == 1 => {
-                HandType::FiveOfKind
-            },
-            count if count.len() == 2 => {
-                if count[0] == 1 || count[0] == 4 {
-                    HandType::FourOfKind
-                } else {
-                    HandType::FullHouse
-                }
-            },
-            count if count.len() == 3 => {
-                if count[0] == 3 || count[1] == 3 || count[2] == 3 {
-                    HandType::ThreeOfKind
-                } else {
-                    HandType::TwoPair
-                }
-            },
-            count if count.len() == 4 => {
-                HandType::OnePair
-            }
-            _ => HandType::HighCard
-        };
-        hands.push(
-            Hand {
-                ty: hand_type,
-                cards: numeric_representation_of_cards,
-                bid: bid.parse().unwrap()
-            }
-        )
-    }
-    hands
+    parse_hands(input, &Rules::jokers())
 }
 
 #[aoc(day7, part2)]
 fn part_two(input: &[Hand]) -> u32 {
-    input
-        .iter()
-        .sorted_unstable()
-        .enumerate()
-        .fold(0, |value, (i, Hand { bid, .. })| {
-            value + (i+1) as u32 * bid
-        })
+    total_winnings(input)
 }
 
 #[cfg(test)]
@@ -272,4 +378,103 @@ mod tests {
         let result = part_two(&input_generator_part_two(input));
         assert_eq!(result, 6839);
     }
+
+    #[test]
+    fn rules_1() {
+        let cards = |hand: &str| -> [char; 5] { hand.chars().collect_vec().try_into().unwrap() };
+
+        let mut rules = Rules::jokers();
+        rules.wild.push('2');
+        assert_eq!(rules.classify(&cards("J2345")), HandType::ThreeOfKind);
+        assert_eq!(rules.classify(&cards("JJ222")), HandType::FiveOfKind);
+
+        rules.type_ranking.retain(|ty| *ty != HandType::FiveOfKind);
+        assert_eq!(rules.classify(&cards("JJ222")), HandType::FourOfKind);
+        assert_eq!(rules.classify(&cards("AAAAK")), HandType::FourOfKind);
+        assert!(Hand::new(cards("JJ222"), 0, &rules) < Hand::new(cards("AAAAK"), 0, &rules));
+
+        let mut rules = Rules::standard();
+        assert!(Hand::new(cards("2A345"), 0, &rules) < Hand::new(cards("3K456"), 0, &rules));
+        rules.tie_break = TieBreak::HighestCard;
+        assert!(Hand::new(cards("2A345"), 0, &rules) > Hand::new(cards("3K456"), 0, &rules));
+    }
+
+    #[test]
+    fn rules_2() {
+        let cards = |hand: &str| -> [char; 5] { hand.chars().collect_vec().try_into().unwrap() };
+
+        let mut rules = Rules::jokers();
+        rules.type_ranking.swap(4, 5);
+        assert_eq!(rules.type_ranking[5], HandType::FullHouse);
+        assert_eq!(
+            rules.substitution(&cards("KKKJ2")),
+            Some(Substitution { wild_as: vec!['2'], played: cards("KKK22"), ty: HandType::FullHouse })
+        );
+        assert_eq!(rules.classify(&cards("KKKK2")), HandType::FourOfKind);
+        assert_eq!(rules.classify(&cards("KKKKK")), HandType::FiveOfKind);
+        assert!(Hand::new(cards("KKKJ2"), 0, &rules) > Hand::new(cards("AAAA2"), 0, &rules));
+
+        rules.type_ranking = vec![HandType::HighCard, HandType::OnePair];
+        assert_eq!(rules.classify(&cards("KKKK2")), HandType::OnePair);
+        assert_eq!(rules.classify(&cards("2345J")), HandType::OnePair);
+        assert_eq!(rules.classify(&cards("23456")), HandType::HighCard);
+        rules.type_ranking = vec![HandType::TwoPair];
+        assert_eq!(rules.classify(&cards("23456")), HandType::HighCard);
+        assert_eq!(rules.classify(&cards("KKKK2")), HandType::FourOfKind);
+        assert_eq!(rules.classify(&cards("KKKJ3")), HandType::TwoPair);
+        assert_eq!(rules.classify(&cards("KKK42")), HandType::ThreeOfKind);
+        assert!(Hand::new(cards("AAA42"), 0, &rules) < Hand::new(cards("22334"), 0, &rules));
+        assert!(Hand::new(cards("23456"), 0, &rules) < Hand::new(cards("AAA42"), 0, &rules));
+        assert!(Hand::new(cards("AKQ98"), 0, &rules) > Hand::new(cards("KKK42"), 0, &rules));
+    }
+
+    #[test]
+    fn explain_1() {
+        let cards = |hand: &str| -> [char; 5] { hand.chars().collect_vec().try_into().unwrap() };
+        let rules = Rules::jokers();
+        let hand = |hand: &str| Hand::new(cards(hand), 0, &rules);
+
+        assert_eq!(
+            rules.explain(&hand("KTJJT"), &hand("QQQJA")),
+            (Ordering::Greater, Decider::Card { position: 0, left: 'K', right: 'Q' })
+        );
+        assert_eq!(
+            rules.explain(&hand("32T3K"), &hand("KK677")),
+            (Ordering::Less, Decider::HandType(HandType::OnePair, HandType::TwoPair))
+        );
+        assert_eq!(
+            rules.explain(&hand("JKKK2"), &hand("QQQQ2")),
+            (Ordering::Less, Decider::Card { position: 0, left: 'J', right: 'Q' })
+        );
+        assert_eq!(rules.explain(&hand("T55J5"), &hand("T55J5")), (Ordering::Equal, Decider::Tie));
+
+        assert_eq!(
+            rules.substitution(&cards("KTJJT")),
+            Some(Substitution { wild_as: vec!['T', 'T'], played: cards("KTTTT"), ty: HandType::FourOfKind })
+        );
+        assert_eq!(
+            rules.substitution(&cards("JJJJJ")),
+            Some(Substitution { wild_as: vec!['A'; 5], played: cards("AAAAA"), ty: HandType::FiveOfKind })
+        );
+        assert_eq!(rules.substitution(&cards("32T3K")), None);
+    }
+
+    #[test]
+    fn ranking_table_1() {
+        let input = indoc! {"
+            32T3K 765
+            T55J5 684
+            KK677 28
+            KTJJT 220
+            QQQJA 483
+        "};
+        assert_eq!(ranking_table(&input_generator_part_two(input)), indoc! {"
+            Rank  Hand   Type          Bid  Winnings
+               1  32T3K  OnePair       765       765
+               2  KK677  TwoPair        28        56
+               3  T55J5  FourOfKind    684      2052
+               4  QQQJA  FourOfKind    483      1932
+               5  KTJJT  FourOfKind    220      1100
+        "});
+    }
 }
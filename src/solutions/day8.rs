@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::env;
 
 use itertools::{
     FoldWhile,
@@ -6,7 +7,9 @@ use itertools::{
 };
 
 use rayon::prelude::*;
-use num::integer::lcm;
+use num::integer::{gcd, lcm};
+
+use crate::cycle::hashed;
 
 #[aoc_generator(day8)]
 fn input_generator(input: &str) -> (String, HashMap<String, (String, String)>) {
@@ -58,8 +61,8 @@ fn part_one(
         .1
 }
 
-#[aoc(day8, part2)]
-fn part_two(
+#[aoc(day8, part2, Lcm)]
+fn part_two_lcm(
     (
         directions,
         mappings): &(String, HashMap<String, (String, String)>
@@ -94,6 +97,172 @@ fn part_two(
         .reduce(|| 1u64, lcm)
 }
 
+/// Where a ghost walking from one node reaches `Z` nodes. Its state is its
+/// node and how far through the instructions it is, so after `tail` steps it
+/// repeats the same `length` states forever.
+#[derive(Debug, PartialEq)]
+pub struct GhostWalk {
+    pub tail: u64,
+    pub length: u64,
+    /// Steps before the cycle that end on a `Z` node.
+    pub tail_hits: Vec<u64>,
+    /// Steps in the first pass through the cycle that end on a `Z` node.
+    pub cycle_hits: Vec<u64>
+}
+
+impl GhostWalk {
+    pub fn new(start: &str, directions: &str, mappings: &HashMap<String, (String, String)>) -> Self {
+        let directions = directions.as_bytes();
+        let step = |&(node, i): &(&str, usize)| {
+            let (left, right) = mappings.get(node).unwrap();
+            let next = if directions[i] == b'L' { left } else { right };
+            (next.as_str(), (i + 1) % directions.len())
+        };
+        let (cycle, states) = hashed(&(start, 0), step);
+
+        let hits = states
+            .iter()
+            .enumerate()
+            .filter(|(_, (node, _))| node.ends_with('Z'))
+            .map(|(steps, _)| steps as u64);
+        let (tail_hits, cycle_hits) = hits.partition(|&steps| steps < cycle.start as u64);
+
+        GhostWalk { tail: cycle.start as u64, length: cycle.length as u64, tail_hits, cycle_hits }
+    }
+
+    /// Whether the ghost is on a `Z` node after this many steps.
+    pub fn hits(&self, steps: u64) -> bool {
+        if steps < self.tail {
+            self.tail_hits.contains(&steps)
+        } else {
+            let steps = self.tail + (steps - self.tail) % self.length;
+            self.cycle_hits.contains(&steps)
+        }
+    }
+
+    /// Whether the ghost reaches a `Z` node exactly every `length` steps from
+    /// the start, which is what the `Lcm` solution assumes.
+    pub fn fits_lcm(&self) -> bool {
+        self.tail_hits.is_empty() && self.cycle_hits == [self.length]
+    }
+}
+
+/// Walks a ghost from every `A` node, sorted by node.
+pub fn ghost_walks(directions: &str, mappings: &HashMap<String, (String, String)>) -> Vec<(String, GhostWalk)> {
+    mappings
+        .keys()
+        .filter(|node| node.ends_with('A'))
+        .sorted()
+        .collect_vec()
+        .into_par_iter()
+        .map(|start| (start.clone(), GhostWalk::new(start, directions, mappings)))
+        .collect()
+}
+
+/// A table of each ghost's walk, with `Z` positions counted from the start of
+/// its cycle, and whether the `Lcm` solution can be trusted.
+pub fn network_report(directions: &str, mappings: &HashMap<String, (String, String)>) -> String {
+    let walks = ghost_walks(directions, mappings);
+    let mut report = String::from("Start   Tail  Cycle  Tail Z  Cycle Z  LCM\n");
+    for (start, walk) in walks.iter() {
+        let cycle_positions = walk.cycle_hits.iter().map(|hit| hit - walk.tail).collect_vec();
+        report.push_str(&format!(
+            "{start:<5} {:>6} {:>6}  {:<7} {:<8} {}\n",
+            walk.tail,
+            walk.length,
+            format!("{:?}", walk.tail_hits),
+            format!("{cycle_positions:?}"),
+            if walk.fits_lcm() { "yes" } else { "no" }
+        ));
+    }
+    let holds = walks.iter().all(|(_, walk)| walk.fits_lcm());
+    report.push_str(&format!("LCM assumptions hold: {}\n", if holds { "yes" } else { "no" }));
+
+    report
+}
+
+/// Combines `x = a1 (mod m1)` and `x = a2 (mod m2)` into one congruence, or
+/// `None` if no number satisfies both. The moduli need not be coprime.
+fn crt((a1, m1): (i128, i128), (a2, m2): (i128, i128)) -> Option<(i128, i128)> {
+    let g = gcd(m1, m2);
+    if (a2 - a1) % g != 0 {
+        return None
+    }
+
+    // Bezout coefficient for m1 / g modulo m2 / g, by the extended Euclidean algorithm.
+    let (m1_g, m2_g) = (m1 / g, m2 / g);
+    let (mut old_r, mut r, mut old_s, mut s) = (m1_g, m2_g, 1, 0);
+    while r != 0 {
+        let quotient = old_r / r;
+        (old_r, r) = (r, old_r - quotient * r);
+        (old_s, s) = (s, old_s - quotient * s);
+    }
+
+    let modulus = m1_g * m2;
+    let k = ((a2 - a1) / g).rem_euclid(m2_g) * old_s.rem_euclid(m2_g) % m2_g;
+    Some(((a1 + m1 * k).rem_euclid(modulus), modulus))
+}
+
+/// The first step where every ghost is on a `Z` node at once, or `None` if
+/// that never happens. Steps before every ghost has reached its cycle are
+/// checked directly; after that each ghost's hits repeat, and they are merged
+/// with the Chinese remainder theorem.
+pub fn ghosts_meet(walks: &[GhostWalk]) -> Option<u64> {
+    let all_hit = |steps: u64| walks.iter().all(|walk| walk.hits(steps));
+    let longest_tail = walks.iter().map(|walk| walk.tail).max()?.max(1);
+
+    let early = walks
+        .iter()
+        .flat_map(|walk| walk.tail_hits.iter().copied())
+        .filter(|&steps| steps >= 1 && steps < longest_tail && all_hit(steps))
+        .min();
+    if early.is_some() {
+        return early
+    }
+
+    let congruences = walks
+        .iter()
+        .try_fold(vec![(0, 1)], |congruences, walk| {
+            let merged = congruences
+                .iter()
+                .cartesian_product(walk.cycle_hits.iter())
+                .filter_map(|(&congruence, &hit)|
+                    crt(congruence, (hit as i128 % walk.length as i128, walk.length as i128))
+                )
+                .unique()
+                .collect_vec();
+            (!merged.is_empty()).then_some(merged)
+        })?;
+
+    congruences
+        .into_iter()
+        .map(|(residue, modulus)| {
+            let below = (longest_tail as i128 - residue).max(0);
+            residue + (below + modulus - 1) / modulus * modulus
+        })
+        .min()
+        .and_then(|steps| u64::try_from(steps).ok())
+}
+
+/// Set `DAY8_REPORT` when running the solution to print each ghost's walk.
+#[aoc(day8, part2)]
+fn part_two(
+    (
+        directions,
+        mappings
+    ): &(String, HashMap<String, (String, String)>)
+) -> u64 {
+    if env::var_os("DAY8_REPORT").is_some() {
+        eprint!("{}", network_report(directions, mappings));
+    }
+    let walks = ghost_walks(directions, mappings)
+        .into_iter()
+        .map(|(_, walk)| walk)
+        .collect_vec();
+
+    ghosts_meet(&walks).expect("Ghosts should all reach Z nodes at the same time")
+}
+
 #[cfg(test)]
 mod tests {
     use indoc::indoc;
@@ -146,4 +315,94 @@ mod tests {
         let result = part_two(&input_generator(input));
         assert_eq!(result, 6);
     }
+
+    #[test]
+    fn part2_2() {
+        let input = indoc! {"
+            LR
+
+            11A = (11B, XXX)
+            11B = (XXX, 11Z)
+            11Z = (11B, XXX)
+            22A = (22B, XXX)
+            22B = (22C, 22C)
+            22C = (22Z, 22Z)
+            22Z = (22B, 22B)
+            XXX = (XXX, XXX)
+        "};
+        let result = part_two_lcm(&input_generator(input));
+        assert_eq!(result, 6);
+    }
+
+    #[test]
+    fn ghost_walk_1() {
+        let (directions, mappings) = input_generator(indoc! {"
+            L
+
+            11A = (11B, 11B)
+            11B = (11Z, 11Z)
+            11Z = (11C, 11C)
+            11C = (11D, 11D)
+            11D = (11E, 11E)
+            11E = (11Z, 11Z)
+            22A = (22Z, 22Z)
+            22Z = (22B, 22B)
+            22B = (2XZ, 2XZ)
+            2XZ = (22C, 22C)
+            22C = (22Z, 22Z)
+        "});
+        let first = GhostWalk::new("11A", &directions, &mappings);
+        assert_eq!(first, GhostWalk { tail: 2, length: 4, tail_hits: vec![], cycle_hits: vec![2] });
+        let second = GhostWalk::new("22A", &directions, &mappings);
+        assert_eq!(second, GhostWalk { tail: 1, length: 4, tail_hits: vec![], cycle_hits: vec![1, 3] });
+
+        // Hits at 2, 6, 10, ... and at 1, 3, 5, 7, ... never line up.
+        assert_eq!(ghosts_meet(&[first, second]), None);
+    }
+
+    #[test]
+    fn ghosts_meet_1() {
+        let walk = |tail, length, tail_hits: &[u64], cycle_hits: &[u64]|
+            GhostWalk { tail, length, tail_hits: tail_hits.to_vec(), cycle_hits: cycle_hits.to_vec() };
+
+        assert_eq!(ghosts_meet(&[walk(0, 6, &[], &[4]), walk(0, 4, &[], &[2])]), Some(10));
+        assert_eq!(ghosts_meet(&[walk(0, 6, &[], &[3]), walk(0, 4, &[], &[2])]), None);
+        assert_eq!(ghosts_meet(&[walk(0, 6, &[], &[1, 3]), walk(0, 4, &[], &[3])]), Some(3));
+        assert_eq!(ghosts_meet(&[walk(5, 3, &[2], &[6]), walk(0, 2, &[], &[0])]), Some(2));
+        assert_eq!(ghosts_meet(&[walk(5, 3, &[], &[6]), walk(0, 5, &[], &[1])]), Some(6));
+        assert_eq!(ghosts_meet(&[walk(0, 1_000_003, &[], &[7]), walk(0, 999_983, &[], &[11])]), Some(
+            (0..).map(|k| 7 + k * 1_000_003).find(|steps| steps % 999_983 == 11).unwrap()
+        ));
+    }
+
+    #[test]
+    fn crt_1() {
+        assert_eq!(crt((2, 3), (3, 5)), Some((8, 15)));
+        assert_eq!(crt((4, 6), (2, 4)), Some((10, 12)));
+        assert_eq!(crt((3, 6), (2, 4)), None);
+        assert_eq!(crt((0, 1), (3, 7)), Some((3, 7)));
+        assert_eq!(crt((3, 7), (3, 7)), Some((3, 7)));
+    }
+
+    #[test]
+    fn network_report_1() {
+        let (directions, mappings) = input_generator(indoc! {"
+            LR
+
+            11A = (11B, XXX)
+            11B = (XXX, 11Z)
+            11Z = (11B, XXX)
+            22A = (22B, XXX)
+            22B = (22C, 22C)
+            22C = (22Z, 22Z)
+            22Z = (22B, 22B)
+            XXX = (XXX, XXX)
+        "});
+        assert_eq!(network_report(&directions, &mappings), indoc! {"
+            Start   Tail  Cycle  Tail Z  Cycle Z  LCM
+            11A        1      2  []      [1]      yes
+            22A        1      6  []      [2, 5]   no
+            LCM assumptions hold: no
+        "});
+    }
 }
@@ -0,0 +1,22 @@
+/// Xorshift generator for randomized tests, seeded so every run sees the same
+/// numbers without pulling in a `rand` dependency.
+pub struct XorShift(u64);
+
+impl XorShift {
+    /// The seed must not be zero, or every number drawn is zero.
+    pub fn new(seed: u64) -> Self {
+        XorShift(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number in `0..bound`.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}